@@ -0,0 +1,31 @@
+//! Times `comp check` on generated scripts of a long chain of functions, each passing its argument to the first one.
+//! The parameter of the first function is undetermined until the last line, and each function binds it to its own,
+//! so that the types bound to one another form a chain as long as the script.
+//! Run with `cargo bench`; the time per function should stay about the same as the chain grows.
+
+use std::{fs, process::Command, time::Instant};
+
+fn main() {
+    for len in [1000, 2000, 4000, 8000, 16000, 32000, 64000] {
+        let mut source = String::from("x0 = (y) => y\n");
+        for i in 1..len {
+            source.push_str(&format!("x{i} = (y) => x0(y)\n"));
+        }
+        source.push_str(&format!("x{}(42)\n", len - 1));
+        let path = std::env::temp_dir().join(format!("comp-long-chain-{len}"));
+        fs::write(&path, source).expect("the script must be written");
+        let start = Instant::now();
+        let status = Command::new(env!("CARGO_BIN_EXE_comp"))
+            .arg("check")
+            .arg(&path)
+            .status()
+            .expect("`comp` must run");
+        let elapsed = start.elapsed();
+        assert!(status.success(), "the script must type-check");
+        fs::remove_file(&path).expect("the script must be removed");
+        println!(
+            "{len:>6} functions: {elapsed:>12.3?} ({:.3?} each)",
+            elapsed / len
+        );
+    }
+}
@@ -1,10 +1,104 @@
-pub enum Expr {
+mod fmt;
+use crate::span::Span;
+
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+pub enum ExprKind {
     Id(Ty),
     Int(i32),
+    Float(f64),
+    Bool(bool),
+    Str(String),
     Var(String),
     Assign(Box<Expr>, Box<Expr>),
     Call(Box<Expr>, Vec<Expr>),
+    /// Two or more components, as in `(a, b)`.
+    Tuple(Vec<Expr>),
+    /// `tuple.index`.
+    Proj(Box<Expr>, usize),
+    Unary(UnOp, Box<Expr>),
     Bin(Box<Expr>, BinOp, Box<Expr>),
+    /// `if cond then a else b`.
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Lambda {
+        params: Vec<Param>,
+        body: Box<Expr>,
+    },
+    /// `{ stmts; value }`, whose `let` bindings are visible only inside it.
+    Block {
+        stmts: Vec<Stmt>,
+        value: Box<Expr>,
+    },
+    /// `match scrutinee { pattern => body, ... }`, which takes the first arm whose pattern matches.
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<Arm>,
+    },
+    /// Stands for source that failed to parse. Never escapes the parser.
+    Error,
+}
+
+pub enum Stmt {
+    /// `let name = value`, which introduces a new variable even if `name` is already bound.
+    Let {
+        param: Param,
+        value: Expr,
+        span: Span,
+    },
+    /// `type Name = Ctor(fields) | ...`, which declares a sum type and its constructors.
+    Type {
+        name: String,
+        ctors: Vec<CtorDecl>,
+        span: Span,
+    },
+    Expr(Expr),
+}
+
+/// One alternative of a `type` declaration, as `Rect(Int, Int)`.
+pub struct CtorDecl {
+    pub name: String,
+    pub fields: Vec<Ty>,
+    pub span: Span,
+}
+
+pub struct Arm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+pub enum PatternKind {
+    /// `_`.
+    Wildcard,
+    /// A name, which binds the matched value unless it is a constructor without fields.
+    Var(String),
+    Int(i32),
+    Bool(bool),
+    /// `Ctor(patterns)`.
+    Ctor(String, Vec<Pattern>),
+    /// Two or more components, as in `(a, b)`.
+    Tuple(Vec<Pattern>),
+}
+
+/// A name bound by a lambda or `let`.
+pub struct Param {
+    pub name: String,
+    /// The type is inferred if it is not annotated.
+    pub ty: Option<Ty>,
+    pub span: Span,
+}
+
+pub enum UnOp {
+    Neg,
+    Not,
 }
 
 pub enum BinOp {
@@ -13,9 +107,30 @@ pub enum BinOp {
     Mul,
     Div,
     Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+pub struct Ty {
+    pub kind: TyKind,
+    pub span: Span,
 }
 
-pub enum Ty {
+pub enum TyKind {
     Int,
+    Float,
+    Bool,
+    Str,
     Func { args: Vec<Ty>, ret: Box<Ty> },
+    Tuple(Vec<Ty>),
+    /// A type declared with `type`.
+    Named(String),
+    /// Stands for source that failed to parse. Never escapes the parser.
+    Error,
 }
@@ -0,0 +1,220 @@
+use super::*;
+use std::fmt::{self, Display, Formatter};
+
+/// Prints the expression fully parenthesized, so that the structure built by the parser is visible.
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Id(ty) => write!(f, "[{ty}]"),
+            ExprKind::Int(value) => write!(f, "{value}"),
+            ExprKind::Float(value) => write!(f, "{value:?}"),
+            ExprKind::Bool(value) => write!(f, "{value}"),
+            ExprKind::Str(value) => write!(f, "{value:?}"),
+            ExprKind::Var(name) => write!(f, "{name}"),
+            ExprKind::Assign(left, right) => write!(f, "({left} = {right})"),
+            ExprKind::Call(func, args) => write!(
+                f,
+                "{func}({})",
+                args.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Unary(op, operand) => write!(f, "({op}{operand})"),
+            ExprKind::Tuple(items) => write!(
+                f,
+                "({})",
+                items
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Proj(tuple, index) => write!(f, "{tuple}.{index}"),
+            ExprKind::Bin(left, op, right) => write!(f, "({left} {op} {right})"),
+            ExprKind::If(cond, a, b) => write!(f, "(if {cond} then {a} else {b})"),
+            ExprKind::Lambda { params, body } => write!(
+                f,
+                "(({}) => {body})",
+                params
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Block { stmts, value } => {
+                write!(f, "{{")?;
+                for stmt in stmts {
+                    write!(f, "{stmt}; ")?;
+                }
+                write!(f, "{value}}}")
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                write!(f, "(match {scrutinee} {{")?;
+                for (i, arm) in arms.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{separator}{} => {}", arm.pattern, arm.body)?;
+                }
+                write!(f, " }})")
+            }
+            ExprKind::Error => write!(f, "<error>"),
+        }
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Let { param, value, .. } => write!(f, "let {param} = {value}"),
+            Stmt::Type { name, ctors, .. } => {
+                write!(f, "type {name} = ")?;
+                for (i, ctor) in ctors.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { " | " };
+                    write!(f, "{separator}{}", ctor.name)?;
+                    if !ctor.fields.is_empty() {
+                        write!(
+                            f,
+                            "({})",
+                            ctor.fields
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+            Stmt::Expr(expr) => write!(f, "{expr}"),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PatternKind::Wildcard => write!(f, "_"),
+            PatternKind::Var(name) => write!(f, "{name}"),
+            PatternKind::Int(value) => write!(f, "{value}"),
+            PatternKind::Bool(value) => write!(f, "{value}"),
+            PatternKind::Ctor(name, args) => write!(
+                f,
+                "{name}({})",
+                args.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            PatternKind::Tuple(items) => write!(
+                f,
+                "({})",
+                items
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.ty {
+            Some(ty) => write!(f, "{}: {ty}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl Display for UnOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UnOp::Neg => write!(f, "-"),
+            UnOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl Display for BinOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BinOp::Add => write!(f, "+"),
+            BinOp::Sub => write!(f, "-"),
+            BinOp::Mul => write!(f, "*"),
+            BinOp::Div => write!(f, "/"),
+            BinOp::Rem => write!(f, "%"),
+            BinOp::Eq => write!(f, "=="),
+            BinOp::Ne => write!(f, "!="),
+            BinOp::Lt => write!(f, "<"),
+            BinOp::Le => write!(f, "<="),
+            BinOp::Gt => write!(f, ">"),
+            BinOp::Ge => write!(f, ">="),
+            BinOp::And => write!(f, "&&"),
+            BinOp::Or => write!(f, "||"),
+        }
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TyKind::Int => write!(f, "Int"),
+            TyKind::Float => write!(f, "Float"),
+            TyKind::Bool => write!(f, "Bool"),
+            TyKind::Str => write!(f, "Str"),
+            TyKind::Func { args, ret } => write!(
+                f,
+                "({}){ret}",
+                args.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TyKind::Tuple(items) => write!(
+                f,
+                "({})",
+                items
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TyKind::Named(name) => write!(f, "{name}"),
+            TyKind::Error => write!(f, "<error>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser;
+
+    /// Each statement of `input` as printed by `--emit=ast`.
+    fn print(input: &str) -> Vec<String> {
+        let (statements, errors) = parser::parse(input, 0);
+        assert!(errors.is_empty(), "`{input}` must parse");
+        statements.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn statements_are_printed_fully_parenthesized() {
+        let input = "x = 1 + 2 * -3\n\
+             let y = (a: Int, b) => { let c = a; c - b }\n\
+             type S = A(Int, Float) | B\n\
+             m = match [S] { A(n, _) => n, B => 0 }\n\
+             t = ((1, \"s\"), [(Int)Bool]).0.1\n\
+             if !true && x < 2 then 1.5 else f(x, 2)";
+        assert_eq!(
+            print(input),
+            [
+                "(x = (1 + (2 * -3)))",
+                "let y = ((a: Int, b) => {let c = a; (c - b)})",
+                "type S = A(Int, Float) | B",
+                "(m = (match [S] { A(n, _) => n, B => 0 }))",
+                "(t = ((1, \"s\"), [(Int)Bool]).0.1)",
+                "(if ((!true) && (x < 2)) then 1.5 else f(x, 2))",
+            ]
+        );
+    }
+}
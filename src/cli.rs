@@ -0,0 +1,140 @@
+pub const USAGE: &str = "\
+Usage: comp [COMMAND] [OPTIONS] [FILE...]
+
+Commands:
+  run FILE...    Evaluate each file and print every statement with its type and value
+  check FILE...  Parse and type-check each file without evaluating it
+  repl           Evaluate each statement from standard input as soon as it is entered
+  help           Print this message
+
+A FILE of `-` reads standard input. Without a command, `comp` runs standard input.
+
+Options:
+  --emit=STAGE[,STAGE...]
+                 Print the output of the given stages instead, where STAGE is one of
+                 `tokens`, `ast`, `ir`, `types`, `derivation-tex` and `values`
+                 (`values` needs `run`)
+  -h, --help     Print this message
+
+Exit status is 0 on success, 1 if any file has an error, and 2 on invalid usage.";
+
+pub enum Command {
+    Run { files: Vec<String>, emit: Vec<Stage> },
+    Check { files: Vec<String>, emit: Vec<Stage> },
+    Repl,
+    Help,
+}
+
+/// A compiler stage whose output can be printed with `--emit`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The tokens read by the lexer.
+    Tokens,
+    /// The syntax tree built by the parser.
+    Ast,
+    /// The IR built by `Context`.
+    Ir,
+    /// The IR annotated with inferred types.
+    Types,
+    /// The typing derivation of each statement, as a `prooftree` of the LaTeX package `bussproofs`.
+    DerivationTex,
+    /// The value of each statement.
+    Values,
+}
+
+/// Parses the command-line arguments, not including the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let Some(command) = args.next() else {
+        return Ok(Command::Run {
+            files: vec!["-".to_string()],
+            emit: Vec::new(),
+        });
+    };
+    if command.starts_with('-') && command != "-h" && command != "--help" {
+        return Err(format!("expected a COMMAND before `{command}`"));
+    }
+    let mut files = Vec::new();
+    let mut emit = Vec::new();
+    for arg in args {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        } else if let Some(stages) = arg.strip_prefix("--emit=") {
+            for stage in stages.split(',') {
+                emit.push(parse_stage(stage)?);
+            }
+        } else if arg.starts_with('-') && arg != "-" {
+            return Err(format!("unknown option `{arg}`"));
+        } else {
+            files.push(arg);
+        }
+    }
+    match &command[..] {
+        "run" | "check" if files.is_empty() => Err(format!("`{command}` needs at least one FILE")),
+        "run" => Ok(Command::Run { files, emit }),
+        "check" if emit.contains(&Stage::Values) => {
+            Err("`--emit=values` needs `run`".to_string())
+        }
+        "check" => Ok(Command::Check { files, emit }),
+        "repl" if !files.is_empty() => Err("`repl` takes no FILE".to_string()),
+        "repl" if !emit.is_empty() => Err("`repl` takes no `--emit`".to_string()),
+        "repl" => Ok(Command::Repl),
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        _ => Err(format!("unknown command `{command}`")),
+    }
+}
+
+fn parse_stage(stage: &str) -> Result<Stage, String> {
+    match stage {
+        "tokens" => Ok(Stage::Tokens),
+        "ast" => Ok(Stage::Ast),
+        "ir" => Ok(Stage::Ir),
+        "types" => Ok(Stage::Types),
+        "derivation-tex" => Ok(Stage::DerivationTex),
+        "values" => Ok(Stage::Values),
+        _ => Err(format!("unknown stage `{stage}` for `--emit`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command, String> {
+        parse_args(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(parse(""), Ok(Command::Run { files, emit }) if files == ["-"] && emit.is_empty()));
+        assert!(matches!(parse("run a -"), Ok(Command::Run { files, .. }) if files == ["a", "-"]));
+        assert!(matches!(parse("check a"), Ok(Command::Check { files, .. }) if files == ["a"]));
+        assert!(matches!(parse("repl"), Ok(Command::Repl)));
+        assert!(matches!(parse("help"), Ok(Command::Help)));
+        assert!(matches!(parse("--help"), Ok(Command::Help)));
+        assert!(matches!(parse("run a -h"), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn emit() {
+        let Ok(Command::Check { emit, .. }) = parse("check --emit=tokens,ir a --emit=derivation-tex") else {
+            panic!("`--emit` must be accepted by `check`");
+        };
+        assert!(emit == [Stage::Tokens, Stage::Ir, Stage::DerivationTex]);
+        assert!(matches!(parse("run a --emit=values"), Ok(Command::Run { emit, .. }) if emit == [Stage::Values]));
+    }
+
+    #[test]
+    fn usage_errors() {
+        let error = |args| parse(args).err().expect("the arguments must be rejected");
+        assert_eq!(error("-"), "expected a COMMAND before `-`");
+        assert_eq!(error("--emit=ast"), "expected a COMMAND before `--emit=ast`");
+        assert_eq!(error("compile a"), "unknown command `compile`");
+        assert_eq!(error("run"), "`run` needs at least one FILE");
+        assert_eq!(error("run a --verbose"), "unknown option `--verbose`");
+        assert_eq!(error("run a --emit=ast,bytecode"), "unknown stage `bytecode` for `--emit`");
+        assert_eq!(error("check a --emit=values"), "`--emit=values` needs `run`");
+        assert_eq!(error("repl a"), "`repl` takes no FILE");
+        assert_eq!(error("repl --emit=ast"), "`repl` takes no `--emit`");
+    }
+}
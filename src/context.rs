@@ -1,93 +1,831 @@
-use crate::{ast, ir};
-use std::collections::HashMap;
+mod pattern;
+use crate::{ast, diagnostic::Diagnostic, ir, span::Span};
+use std::{collections::HashMap, rc::Rc};
 
+#[derive(Clone)]
 pub struct Context {
+    /// The variable each name refers to. A name bound again by `let` refers to the new one.
     variables_name: HashMap<String, usize>,
     num_variables: usize,
+    /// The name of each variable, including those shadowed by `let`.
+    variables_names: Vec<String>,
+    /// Where each variable first appears.
+    /// The parameters of the lambdas and the `let` bindings of the blocks being translated, innermost last.
+    scopes: Vec<Scope>,
+    /// The local names in scope at each global variable that the last statement refers to from inside a lambda or a block.
+    locals_in_scope: Vec<(Span, Vec<String>)>,
+    /// The types declared with `type`. A name declared again refers to the new type.
+    types: HashMap<String, ir::Ty>,
+    ctors: HashMap<String, Rc<ir::Ctor>>,
+    /// The constructors of each declared type, indexed by its `id`.
+    adts: Vec<Vec<Rc<ir::Ctor>>>,
+    /// Where the types in the translated expressions are made.
+    tys: ir::TyTable,
+}
+
+#[derive(Clone, Default)]
+struct Scope {
+    names: HashMap<String, usize>,
+    tys: Vec<ir::Ty>,
+}
+
+pub enum TranslateError {
+    NotAnLvalue(Span),
+    DuplicateParameter(String, Span),
+    /// A variable may be read before it is assigned. `suggestion` is a similar name that can be read there.
+    Unassigned {
+        name: String,
+        span: Span,
+        suggestion: Option<String>,
+    },
+    UnknownType(String, Span),
+    UnknownConstructor(String, Span),
+    ConstructorArity {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    DuplicateConstructor(String, Span),
+    /// A name appears twice in one pattern.
+    DuplicateBinding(String, Span),
+    /// Patterns in the same position of a `match` that cannot match values of the same type.
+    MismatchedPatterns {
+        span: Span,
+        first: Span,
+    },
+    /// No arm of the `match` matches `witness`.
+    NonExhaustive {
+        witness: String,
+        span: Span,
+    },
 }
 
 impl Context {
-    pub fn new() -> Context {
+    pub fn new(tys: &ir::TyTable) -> Context {
         Context {
             variables_name: HashMap::new(),
             num_variables: 0,
+            variables_names: Vec::new(),
+            scopes: Vec::new(),
+            locals_in_scope: Vec::new(),
+            types: HashMap::new(),
+            ctors: HashMap::new(),
+            adts: Vec::new(),
+            tys: tys.clone(),
         }
     }
     pub fn num_variables(&self) -> usize {
         self.num_variables
     }
-    pub fn translate_expr(&mut self, expr: ast::Expr) -> ir::Expr {
-        match expr {
-            ast::Expr::Id(ty) => ir::Expr::Func {
-                func: ir::Func::Id(translate_ty(ty)),
+    /// The name of every variable, in the order they were introduced.
+    pub fn variables(&self) -> Vec<(&str, usize)> {
+        let mut variables: Vec<_> = self
+            .variables_name
+            .iter()
+            .map(|(name, &idx)| (&name[..], idx))
+            .collect();
+        variables.sort_by_key(|&(_, idx)| idx);
+        variables
+    }
+    /// Reports a read of a variable that may happen before it is assigned in `expr`, a translated statement.
+    /// `assigned` tells which variables were assigned by the earlier statements, and is updated with those assigned by `expr`.
+    pub fn check_assigned(
+        &self,
+        expr: &ir::Expr,
+        assigned: &mut [bool],
+    ) -> Result<(), TranslateError> {
+        let assigned_before = assigned.to_vec();
+        let Some((idx, span)) = expr.unassigned_read(assigned) else {
+            return Ok(());
+        };
+        let name = &self.variables_names[idx];
+        let locals = self
+            .locals_in_scope
+            .iter()
+            .find(|&&(read, _)| read == span)
+            .map_or(&[][..], |(_, names)| &names[..]);
+        let len = name.chars().count();
+        let suggestion = self
+            .variables_name
+            .iter()
+            .filter(|&(_, &other_idx)| assigned_before[other_idx])
+            .map(|(other, _)| other)
+            .chain(locals)
+            .filter(|&other| other != name)
+            .map(|other| (edit_distance(name, other), other))
+            .filter(|&(distance, _)| distance <= len.max(3) / 3 && distance < len)
+            .min()
+            .map(|(_, other)| other.clone());
+        Err(TranslateError::Unassigned {
+            name: name.clone(),
+            span,
+            suggestion,
+        })
+    }
+    /// Forgets the global variables introduced after the first `num_variables`,
+    /// as those of a statement that failed, so that an old variable of the same name is visible again.
+    pub fn forget_variables(&mut self, num_variables: usize) {
+        let forgotten: Vec<_> = self.variables_names.drain(num_variables..).collect();
+        for name in forgotten {
+            match self.variables_names.iter().rposition(|other| *other == name) {
+                Some(idx) => self.variables_name.insert(name, idx),
+                None => self.variables_name.remove(&name),
+            };
+        }
+        self.num_variables = num_variables;
+    }
+    /// Translates a statement. A `let` outside of any block introduces a new global variable.
+    /// A `type` declaration, which is visible everywhere after it, leaves nothing to evaluate.
+    pub fn translate_stmt(&mut self, stmt: ast::Stmt) -> Result<Option<ir::Expr>, TranslateError> {
+        if self.scopes.is_empty() {
+            self.locals_in_scope.clear();
+        }
+        let (param, value, span) = match stmt {
+            ast::Stmt::Let { param, value, span } => (param, value, span),
+            ast::Stmt::Type { name, ctors, .. } => {
+                self.declare_type(name, ctors)?;
+                return Ok(None);
+            }
+            ast::Stmt::Expr(expr) => return self.translate_expr(expr).map(Some),
+        };
+        let value = self.translate_expr(value)?;
+        let ty = match param.ty {
+            Some(ty) => self.translate_ty(ty)?,
+            None => self.tys.new_ty(ir::TyInner::Undetermined),
+        };
+        // The name is bound only after the value is translated, so that the value sees the old binding.
+        // The annotated type goes to exactly one place, either the local variable or `Assign`.
+        let (reference, ty) = match self.scopes.last_mut() {
+            Some(scope) => {
+                let index = scope.tys.len();
+                scope.names.insert(param.name, index);
+                scope.tys.push(ty.clone());
+                let local = ir::ExprKind::Local {
+                    depth: 0,
+                    index,
+                    ty,
+                };
+                (local, self.tys.new_ty(ir::TyInner::Undetermined))
+            }
+            None => (
+                ir::ExprKind::Var(self.new_variable(param.name)),
+                ty,
+            ),
+        };
+        let assignment = ir::Expr {
+            kind: ir::ExprKind::Func {
+                func: ir::Func::Assign(ty),
+                calls: vec![ir::Call::new(
+                    vec![
+                        ir::Expr {
+                            kind: reference,
+                            span: param.span,
+                        },
+                        value,
+                    ],
+                    span,
+                )],
+            },
+            span,
+        };
+        // Outside of any block, the statement gives the value as `x = value` does, rather than the variable.
+        if self.scopes.is_empty() {
+            Ok(Some(deref(&self.tys, assignment, span)))
+        } else {
+            Ok(Some(assignment))
+        }
+    }
+    fn declare_type(
+        &mut self,
+        name: String,
+        ctors: Vec<ast::CtorDecl>,
+    ) -> Result<(), TranslateError> {
+        for (i, ctor) in ctors.iter().enumerate() {
+            if ctors[..i].iter().any(|other| other.name == ctor.name) {
+                return Err(TranslateError::DuplicateConstructor(ctor.name.clone(), ctor.span));
+            }
+        }
+        let id = self.adts.len();
+        let ty = self.tys.new_ty(ir::TyInner::Named {
+            id,
+            name: name.as_str().into(),
+        });
+        // The name is bound before the fields are translated, so that a field can be of the type itself.
+        let prev = self.types.insert(name.clone(), ty.clone());
+        let ctors = ctors
+            .into_iter()
+            .map(|ctor| {
+                let fields = ctor
+                    .fields
+                    .into_iter()
+                    .map(|field| self.translate_ty(field))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((ctor.name, fields))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let ctors = match ctors {
+            Ok(ctors) => ctors,
+            Err(err) => {
+                match prev {
+                    Some(prev) => self.types.insert(name, prev),
+                    None => self.types.remove(&name),
+                };
+                return Err(err);
+            }
+        };
+        let ctors: Vec<_> = ctors
+            .into_iter()
+            .enumerate()
+            .map(|(tag, (name, fields))| {
+                Rc::new(ir::Ctor {
+                    name,
+                    tag,
+                    adt: id,
+                    fields,
+                    ty: ty.clone(),
+                })
+            })
+            .collect();
+        for ctor in &ctors {
+            self.ctors.insert(ctor.name.clone(), ctor.clone());
+        }
+        self.adts.push(ctors);
+        Ok(())
+    }
+    fn new_variable(&mut self, name: String) -> usize {
+        let idx = self.num_variables;
+        self.num_variables += 1;
+        self.variables_name.insert(name.clone(), idx);
+        self.variables_names.push(name);
+        idx
+    }
+    pub fn translate_expr(&mut self, expr: ast::Expr) -> Result<ir::Expr, TranslateError> {
+        let kind = match expr.kind {
+            ast::ExprKind::Id(ty) => ir::ExprKind::Func {
+                func: ir::Func::Id(self.translate_ty(ty)?),
                 calls: Vec::new(),
             },
-            ast::Expr::Int(value) => ir::Expr::Int(value),
-            ast::Expr::Call(func, args) => {
-                let mut ret = self.translate_expr(*func);
-                if let ir::Expr::Func { ref mut calls, .. } = ret {
-                    calls.push(ir::Call {
-                        args: args
-                            .into_iter()
-                            .map(|arg| self.translate_expr(arg))
-                            .collect(),
-                    });
+            ast::ExprKind::Var(ref name) if self.builtin(name).is_some() => ir::ExprKind::Func {
+                func: self.builtin(name).unwrap(),
+                calls: Vec::new(),
+            },
+            ast::ExprKind::Var(ref name) if self.ctors.contains_key(name) => {
+                let ctor = self.ctors[name].clone();
+                // A constructor without fields stands for the value it builds.
+                let calls = if ctor.fields.is_empty() {
+                    vec![ir::Call::new(Vec::new(), expr.span)]
                 } else {
-                    panic!("not a function");
+                    Vec::new()
+                };
+                ir::ExprKind::Func {
+                    func: ir::Func::Ctor(ctor),
+                    calls,
                 }
-                ret
             }
-            ast::Expr::Bin(left, op, right) => ir::Expr::Func {
+            ast::ExprKind::Int(value) => ir::ExprKind::Int(value),
+            ast::ExprKind::Float(value) => ir::ExprKind::Float(value),
+            ast::ExprKind::Bool(value) => ir::ExprKind::Bool(value),
+            ast::ExprKind::Str(value) => ir::ExprKind::Str(value.into()),
+            ast::ExprKind::Call(func, args) => {
+                let mut ret = self.translate_expr(*func)?;
+                if !matches!(ret.kind, ir::ExprKind::Func { .. }) {
+                    // Only a `Func` holds calls, so a callee such as a lambda or a block is applied to the identity first.
+                    let span = ret.span;
+                    ret = ir::Expr {
+                        kind: ir::ExprKind::Func {
+                            func: ir::Func::Id(self.tys.new_ty(ir::TyInner::Undetermined)),
+                            calls: vec![ir::Call::new(vec![ret], span)],
+                        },
+                        span,
+                    };
+                }
+                let ir::ExprKind::Func { ref mut calls, .. } = ret.kind else {
+                    unreachable!()
+                };
+                calls.push(ir::Call::new(
+                    args.into_iter()
+                        .map(|arg| self.translate_expr(arg))
+                        .collect::<Result<_, _>>()?,
+                    expr.span,
+                ));
+                ret.kind
+            }
+            ast::ExprKind::Unary(op, operand) => ir::ExprKind::Func {
+                func: match op {
+                    ast::UnOp::Neg => ir::Func::Neg,
+                    ast::UnOp::Not => ir::Func::Not,
+                },
+                calls: vec![ir::Call::new(
+                    vec![self.translate_expr(*operand)?],
+                    expr.span,
+                )],
+            },
+            ast::ExprKind::Bin(left, op, right) => ir::ExprKind::Func {
                 func: match op {
                     ast::BinOp::Add => ir::Func::Add,
                     ast::BinOp::Sub => ir::Func::Sub,
                     ast::BinOp::Mul => ir::Func::Mul,
                     ast::BinOp::Div => ir::Func::Div,
                     ast::BinOp::Rem => ir::Func::Rem,
+                    ast::BinOp::Eq => ir::Func::Eq,
+                    ast::BinOp::Ne => ir::Func::Ne,
+                    ast::BinOp::Lt => ir::Func::Lt,
+                    ast::BinOp::Le => ir::Func::Le,
+                    ast::BinOp::Gt => ir::Func::Gt,
+                    ast::BinOp::Ge => ir::Func::Ge,
+                    ast::BinOp::And => ir::Func::And,
+                    ast::BinOp::Or => ir::Func::Or,
                 },
-                calls: vec![ir::Call {
-                    args: vec![self.translate_expr(*left), self.translate_expr(*right)],
-                }],
+                calls: vec![ir::Call::new(
+                    vec![self.translate_expr(*left)?, self.translate_expr(*right)?],
+                    expr.span,
+                )],
+            },
+            ast::ExprKind::Tuple(items) => ir::ExprKind::Tuple(
+                items
+                    .into_iter()
+                    .map(|item| self.translate_expr(item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ast::ExprKind::Proj(tuple, index) => ir::ExprKind::Func {
+                func: ir::Func::Proj(index),
+                calls: vec![ir::Call::new(
+                    vec![self.translate_expr(*tuple)?],
+                    expr.span,
+                )],
+            },
+            ast::ExprKind::Assign(left, right)
+                if matches!(left.kind, ast::ExprKind::Tuple(_)) =>
+            {
+                // `a, b = value` becomes a block that keeps `value` in a hidden variable and assigns each component of it.
+                let temp_ty = self.tys.new_ty(ir::TyInner::Undetermined);
+                self.scopes.push(Scope {
+                    names: HashMap::new(),
+                    tys: vec![temp_ty.clone()],
+                });
+                let body = self.translate_destructuring(*left, *right, &temp_ty, expr.span);
+                self.scopes.pop();
+                ir::ExprKind::Block {
+                    locals: vec![temp_ty],
+                    body: body?,
+                }
+            }
+            ast::ExprKind::If(cond, a, b) => ir::ExprKind::Func {
+                func: ir::Func::If(self.tys.new_ty(ir::TyInner::Undetermined)),
+                calls: vec![ir::Call::new(
+                    vec![
+                        self.translate_expr(*cond)?,
+                        self.translate_expr(*a)?,
+                        self.translate_expr(*b)?,
+                    ],
+                    expr.span,
+                )],
+            },
+            ast::ExprKind::Lambda { params, body } => {
+                let mut scope = Scope::default();
+                for param in params {
+                    if scope.names.contains_key(&param.name) {
+                        return Err(TranslateError::DuplicateParameter(param.name, param.span));
+                    }
+                    scope.names.insert(param.name, scope.tys.len());
+                    scope.tys.push(match param.ty {
+                        Some(ty) => self.translate_ty(ty)?,
+                        None => self.tys.new_ty(ir::TyInner::Undetermined),
+                    });
+                }
+                let params = scope.tys.clone();
+                self.scopes.push(scope);
+                let body = self.translate_expr(*body);
+                self.scopes.pop();
+                ir::ExprKind::Lambda {
+                    params,
+                    body: Rc::new(body?),
+                }
+            }
+            ast::ExprKind::Block { stmts, value } => {
+                self.scopes.push(Scope::default());
+                let body = self.translate_block(stmts, *value);
+                let scope = self.scopes.pop().expect("the scope of the block was pushed");
+                ir::ExprKind::Block {
+                    locals: scope.tys,
+                    body: body?,
+                }
+            }
+            ast::ExprKind::Match { scrutinee, arms } => {
+                self.translate_match(*scrutinee, arms, expr.span)?
+            }
+            kind => {
+                let reference = self.translate_ref(ast::Expr {
+                    kind,
+                    span: expr.span,
+                })?;
+                ir::ExprKind::Func {
+                    func: ir::Func::Deref(self.tys.new_ty(ir::TyInner::Undetermined)),
+                    calls: vec![ir::Call::new(vec![reference], expr.span)],
+                }
+            }
+        };
+        Ok(ir::Expr {
+            kind,
+            span: expr.span,
+        })
+    }
+    /// The built-in function that `name` refers to, unless a variable of the name hides it.
+    fn builtin(&self, name: &str) -> Option<ir::Func> {
+        if self.variables_name.contains_key(name)
+            || self.scopes.iter().any(|scope| scope.names.contains_key(name))
+        {
+            return None;
+        }
+        match name {
+            "len" => Some(ir::Func::Len),
+            "str" => Some(ir::Func::ToStr),
+            _ => None,
+        }
+    }
+    /// Translates the statements of a block in its own scope, which must have been pushed.
+    fn translate_block(
+        &mut self,
+        stmts: Vec<ast::Stmt>,
+        value: ast::Expr,
+    ) -> Result<Vec<ir::Expr>, TranslateError> {
+        let mut body = Vec::new();
+        for stmt in stmts {
+            body.extend(self.translate_stmt(stmt)?);
+        }
+        body.push(self.translate_expr(value)?);
+        Ok(body)
+    }
+    /// Translates `match` into a block that keeps the value of `scrutinee` in a hidden variable
+    /// and tests it against each pattern in turn with `if`.
+    /// The arm that binds names gets a block of its own, where each name is assigned its part of the value.
+    fn translate_match(
+        &mut self,
+        scrutinee: ast::Expr,
+        arms: Vec<ast::Arm>,
+        span: Span,
+    ) -> Result<ir::ExprKind, TranslateError> {
+        let mut pats = Vec::new();
+        let mut bodies = Vec::new();
+        for arm in arms {
+            let mut bindings = Vec::new();
+            pats.push(self.resolve_pattern(arm.pattern, &mut Vec::new(), &mut bindings)?);
+            bodies.push((bindings, arm.body, arm.span));
+        }
+        self.check_exhaustive(&pats, span)?;
+        let temp_ty = self.tys.new_ty(ir::TyInner::Undetermined);
+        self.scopes.push(Scope {
+            names: HashMap::new(),
+            tys: vec![temp_ty.clone()],
+        });
+        let scrutinee = self.translate_expr(scrutinee);
+        let arms = pats
+            .iter()
+            .zip(bodies)
+            .map(|(pat, (bindings, body, span))| {
+                let test = pattern::test(&self.tys, pat, &mut Vec::new(), &temp_ty);
+                Ok((test, self.translate_arm(bindings, body, &temp_ty)?, span))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        self.scopes.pop();
+        let scrutinee = scrutinee?;
+        // The arms after one that always matches are never reached, and neither is the test of the last arm.
+        let chain = arms?
+            .into_iter()
+            .rev()
+            .fold(None, |rest, (test, body, arm_span)| match (test, rest) {
+                (Some(test), Some(rest)) => Some(ir::Expr {
+                    kind: ir::ExprKind::Func {
+                        func: ir::Func::If(self.tys.new_ty(ir::TyInner::Undetermined)),
+                        calls: vec![ir::Call::new(vec![test, body, rest], arm_span)],
+                    },
+                    span: arm_span,
+                }),
+                _ => Some(body),
+            })
+            .expect("an exhaustive `match` must have an arm");
+        let temp = ir::Expr {
+            kind: ir::ExprKind::Local {
+                depth: 0,
+                index: 0,
+                ty: temp_ty.clone(),
+            },
+            span,
+        };
+        Ok(ir::ExprKind::Block {
+            locals: vec![temp_ty],
+            body: vec![assign(&self.tys, temp, scrutinee, span), chain],
+        })
+    }
+    /// Translates the body of an arm, in a block of its own if the pattern binds names.
+    fn translate_arm(
+        &mut self,
+        bindings: Vec<pattern::Binding>,
+        body: ast::Expr,
+        temp_ty: &ir::Ty,
+    ) -> Result<ir::Expr, TranslateError> {
+        if bindings.is_empty() {
+            return self.translate_expr(body);
+        }
+        let span = body.span;
+        let mut scope = Scope::default();
+        let mut assignments = Vec::new();
+        for (index, binding) in bindings.iter().enumerate() {
+            let ty = self.tys.new_ty(ir::TyInner::Undetermined);
+            scope.names.insert(binding.name().to_string(), index);
+            scope.tys.push(ty.clone());
+            assignments.push(binding.assign(&self.tys, index, &ty, temp_ty, 1));
+        }
+        self.scopes.push(scope);
+        let body = self.translate_expr(body);
+        let scope = self.scopes.pop().expect("the scope of the arm was pushed");
+        assignments.push(body?);
+        Ok(ir::Expr {
+            kind: ir::ExprKind::Block {
+                locals: scope.tys,
+                body: assignments,
+            },
+            span,
+        })
+    }
+    fn translate_ty(&self, ty: ast::Ty) -> Result<ir::Ty, TranslateError> {
+        Ok(match ty.kind {
+            ast::TyKind::Int => self.tys.new_ty(ir::TyInner::Int),
+            ast::TyKind::Float => self.tys.new_ty(ir::TyInner::Float),
+            ast::TyKind::Bool => self.tys.new_ty(ir::TyInner::Bool),
+            ast::TyKind::Str => self.tys.new_ty(ir::TyInner::Str),
+            ast::TyKind::Func { args, ret } => self.tys.new_ty(ir::TyInner::Func {
+                args: args
+                    .into_iter()
+                    .map(|arg| self.translate_ty(arg))
+                    .collect::<Result<_, _>>()?,
+                ret: self.translate_ty(*ret)?,
+            }),
+            ast::TyKind::Tuple(items) => self.tys.new_ty(ir::TyInner::Tuple(
+                items
+                    .into_iter()
+                    .map(|item| self.translate_ty(item))
+                    .collect::<Result<_, _>>()?,
+            )),
+            ast::TyKind::Named(name) => match self.types.get(&name) {
+                Some(ty) => ty.clone(),
+                None => return Err(TranslateError::UnknownType(name, ty.span)),
             },
-            _ => ir::Expr::Func {
-                func: ir::Func::Deref(ir::Ty::new(ir::TyInner::Undetermined)),
-                calls: vec![ir::Call {
-                    args: vec![self.translate_ref(expr)],
-                }],
+            ast::TyKind::Error => unreachable!("erroneous type escaped the parser"),
+        })
+    }
+    /// Translates the body of the block for `targets = value`, in whose scope the hidden variable of type `temp_ty` is.
+    fn translate_destructuring(
+        &mut self,
+        targets: ast::Expr,
+        value: ast::Expr,
+        temp_ty: &ir::Ty,
+        span: Span,
+    ) -> Result<Vec<ir::Expr>, TranslateError> {
+        let temp = || ir::Expr {
+            kind: ir::ExprKind::Local {
+                depth: 0,
+                index: 0,
+                ty: temp_ty.clone(),
             },
+            span,
+        };
+        let value = self.translate_expr(value)?;
+        let mut body = vec![assign(&self.tys, temp(), value, span)];
+        self.translate_targets(targets, &mut Vec::new(), &temp, &mut body)?;
+        body.push(deref(&self.tys, temp(), span));
+        Ok(body)
+    }
+    /// Appends to `body` the assignment to `target` of the component at `path` of the hidden variable `temp`.
+    /// Each step of `path` is a tuple pattern, with its span and number of components, and the index of a component in it.
+    fn translate_targets(
+        &mut self,
+        target: ast::Expr,
+        path: &mut Vec<(Span, usize, usize)>,
+        temp: &dyn Fn() -> ir::Expr,
+        body: &mut Vec<ir::Expr>,
+    ) -> Result<(), TranslateError> {
+        let span = target.span;
+        let ast::ExprKind::Tuple(items) = target.kind else {
+            let reference = self.translate_ref(target)?;
+            let call = |func, arg, span| ir::Expr {
+                kind: ir::ExprKind::Func {
+                    func,
+                    calls: vec![ir::Call::new(vec![arg], span)],
+                },
+                span,
+            };
+            let value = path.iter().fold(deref(&self.tys, temp(), span), |value, &(tuple_span, len, index)| {
+                let tuple = call(ir::Func::Unpack(len), value, tuple_span);
+                call(ir::Func::Proj(index), tuple, span)
+            });
+            body.push(assign(&self.tys, reference, value, span));
+            return Ok(());
+        };
+        let len = items.len();
+        for (index, item) in items.into_iter().enumerate() {
+            path.push((span, len, index));
+            self.translate_targets(item, path, temp, body)?;
+            path.pop();
         }
+        Ok(())
     }
-    fn translate_ref(&mut self, expr: ast::Expr) -> ir::Expr {
-        match expr {
-            ast::Expr::Var(name) => {
-                let var_idx = *self.variables_name.entry(name).or_insert_with(|| {
-                    let new_idx = self.num_variables;
-                    self.num_variables += 1;
-                    new_idx
+    fn translate_ref(&mut self, expr: ast::Expr) -> Result<ir::Expr, TranslateError> {
+        let kind = match expr.kind {
+            ast::ExprKind::Var(ref name) if self.ctors.contains_key(name) => {
+                return Err(TranslateError::NotAnLvalue(expr.span));
+            }
+            ast::ExprKind::Var(name) => {
+                let local = self.scopes.iter().rev().enumerate().find_map(|(depth, scope)| {
+                    let index = *scope.names.get(&name)?;
+                    Some(ir::ExprKind::Local {
+                        depth,
+                        index,
+                        ty: scope.tys[index].clone(),
+                    })
                 });
-                ir::Expr::Var(var_idx)
-            }
-            ast::Expr::Assign(left, right) => {
-                let left = self.translate_ref(*left);
-                let right = self.translate_expr(*right);
-                ir::Expr::Func {
-                    func: ir::Func::Assign(ir::Ty::new(ir::TyInner::Undetermined)),
-                    calls: vec![ir::Call {
-                        args: vec![left, right],
-                    }],
+                if let Some(local) = local {
+                    return Ok(ir::Expr {
+                        kind: local,
+                        span: expr.span,
+                    });
+                }
+                if !self.scopes.is_empty() {
+                    let locals = self.scopes.iter().flat_map(|scope| scope.names.keys().cloned());
+                    self.locals_in_scope.push((expr.span, locals.collect()));
+                }
+                let var_idx = match self.variables_name.get(&name) {
+                    Some(&idx) => idx,
+                    None => self.new_variable(name),
+                };
+                ir::ExprKind::Var(var_idx)
+            }
+            ast::ExprKind::Assign(left, right) => {
+                let left = self.translate_ref(*left)?;
+                let right = self.translate_expr(*right)?;
+                ir::ExprKind::Func {
+                    func: ir::Func::Assign(self.tys.new_ty(ir::TyInner::Undetermined)),
+                    calls: vec![ir::Call::new(vec![left, right], expr.span)],
+                }
+            }
+            _ => return Err(TranslateError::NotAnLvalue(expr.span)),
+        };
+        Ok(ir::Expr {
+            kind,
+            span: expr.span,
+        })
+    }
+}
+
+impl TranslateError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            TranslateError::NotAnLvalue(span) => {
+                Diagnostic::error("invalid left-hand side of assignment", *span)
+                    .with_label("cannot assign to this expression")
+            }
+            TranslateError::DuplicateParameter(name, span) => {
+                Diagnostic::error(format!("parameter `{name}` is declared twice"), *span)
+            }
+            TranslateError::Unassigned {
+                name,
+                span,
+                suggestion,
+            } => {
+                let diagnostic =
+                    Diagnostic::error(format!("variable `{name}` read before it is assigned"), *span);
+                match suggestion {
+                    Some(suggestion) => diagnostic.with_label(format!("did you mean `{suggestion}`?")),
+                    None => diagnostic,
                 }
             }
-            _ => panic!("not a lvalue"),
+            TranslateError::UnknownType(name, span) => {
+                Diagnostic::error(format!("unknown type `{name}`"), *span)
+            }
+            TranslateError::UnknownConstructor(name, span) => {
+                Diagnostic::error(format!("unknown constructor `{name}`"), *span)
+            }
+            TranslateError::ConstructorArity {
+                name,
+                expected,
+                found,
+                span,
+            } => Diagnostic::error(
+                format!("constructor `{name}` has {expected} field(s) but the pattern has {found}"),
+                *span,
+            ),
+            TranslateError::DuplicateConstructor(name, span) => {
+                Diagnostic::error(format!("constructor `{name}` is declared twice"), *span)
+            }
+            TranslateError::DuplicateBinding(name, span) => {
+                Diagnostic::error(format!("`{name}` is bound twice in the same pattern"), *span)
+            }
+            TranslateError::MismatchedPatterns { span, first } => {
+                Diagnostic::error("mismatched patterns", *span)
+                    .with_label(format!("this cannot match the same type as the pattern at {first}"))
+            }
+            TranslateError::NonExhaustive { witness, span } => {
+                Diagnostic::error("non-exhaustive patterns", *span)
+                    .with_label(format!("`{witness}` not covered"))
+            }
         }
     }
 }
 
-fn translate_ty(ty: ast::Ty) -> ir::Ty {
-    match ty {
-        ast::Ty::Int => ir::Ty::new(ir::TyInner::Int),
-        ast::Ty::Func { args, ret } => ir::Ty::new(ir::TyInner::Func {
-            args: args.into_iter().map(translate_ty).collect(),
-            ret: translate_ty(*ret),
-        }),
+fn assign(tys: &ir::TyTable, reference: ir::Expr, value: ir::Expr, span: Span) -> ir::Expr {
+    ir::Expr {
+        kind: ir::ExprKind::Func {
+            func: ir::Func::Assign(tys.new_ty(ir::TyInner::Undetermined)),
+            calls: vec![ir::Call::new(vec![reference, value], span)],
+        },
+        span,
+    }
+}
+
+fn deref(tys: &ir::TyTable, reference: ir::Expr, span: Span) -> ir::Expr {
+    ir::Expr {
+        kind: ir::ExprKind::Func {
+            func: ir::Func::Deref(tys.new_ty(ir::TyInner::Undetermined)),
+            calls: vec![ir::Call::new(vec![reference], span)],
+        },
+        span,
+    }
+}
+
+/// The number of characters to insert, delete or replace, or of adjacent pairs to swap, to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `dist[i][j]` is the distance between the first `i` characters of `a` and the first `j` of `b`.
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            dist[i][j] = match (i, j) {
+                (0, _) => j,
+                (_, 0) => i,
+                _ => {
+                    let replace = dist[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+                    let mut min = replace.min(dist[i - 1][j] + 1).min(dist[i][j - 1] + 1);
+                    if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                        min = min.min(dist[i - 2][j - 2] + 1);
+                    }
+                    min
+                }
+            };
+        }
+    }
+    dist[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    /// Translates each statement of `input` that is not a `type` declaration and checks that it reads only assigned variables,
+    /// giving its IR or the error message.
+    fn translate(input: &str) -> Vec<String> {
+        let (statements, errors) = parser::parse(input, 0);
+        assert!(errors.is_empty(), "`{input}` must parse");
+        let mut context = Context::new(&ir::TyTable::new());
+        let mut assigned = Vec::new();
+        let mut results = Vec::new();
+        for statement in statements {
+            let result = context.translate_stmt(statement).and_then(|expr| {
+                assigned.resize(context.num_variables(), false);
+                if let Some(ref expr) = expr {
+                    context.check_assigned(expr, &mut assigned)?;
+                }
+                Ok(expr)
+            });
+            match result {
+                Ok(expr) => results.extend(expr.map(|expr| expr.to_string())),
+                Err(err) => results.push(err.to_diagnostic().message),
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn lets_are_scoped_to_their_block() {
+        assert_eq!(
+            translate(
+                "x = 1\n\
+                 y = { let x = 2; let x = x + 1; let zed = x; zed }\n\
+                 x\n\
+                 zed\n\
+                 f = (a: Int) => { let b = a; (c: Int) => b + c }\n\
+                 let w = 5"
+            ),
+            [
+                "Deref[?](Assign[?](&v0, 1))",
+                "Deref[?](Assign[?](&v1, Block[?, ?, ?](Assign[?](&l0.0, 2); Assign[?](&l0.1, Add(Deref[?](&l0.0), 1)); Assign[?](&l0.2, Deref[?](&l0.1)); Deref[?](&l0.2))))",
+                "Deref[?](&v0)",
+                "variable `zed` read before it is assigned",
+                "Deref[?](Assign[?](&v3, Lambda[Int](Block[?](Assign[?](&l0.0, Deref[?](&l1.0)); Lambda[Int](Add(Deref[?](&l1.0), Deref[?](&l0.0)))))))",
+                "Deref[?](Assign[?](&v4, 5))",
+            ]
+        );
     }
 }
@@ -0,0 +1,466 @@
+use super::*;
+use std::{
+    fmt::{self, Display, Formatter},
+    iter,
+};
+
+/// A pattern whose constructors are resolved. The names in it are collected separately as `Binding`s.
+#[derive(Clone)]
+pub(super) struct Pat {
+    /// `None` for a wildcard or a name, which match anything.
+    head: Option<Head>,
+    args: Vec<Pat>,
+    span: Span,
+}
+
+/// What a pattern requires of the outermost part of a value.
+#[derive(Clone)]
+enum Head {
+    Int(i32),
+    Bool(bool),
+    /// A tuple of that many components, which every value of the type is.
+    Tuple(usize),
+    Data(Rc<ir::Ctor>),
+}
+
+/// How to reach a part of the matched value from the whole.
+#[derive(Clone)]
+pub(super) enum Step {
+    Proj(usize),
+    Field(Rc<ir::Ctor>, usize),
+}
+
+/// A name in a pattern, bound to the part of the matched value at `path`.
+pub(super) struct Binding {
+    name: String,
+    path: Vec<Step>,
+    span: Span,
+}
+
+/// A value that no pattern matches, as reported by the exhaustiveness check.
+enum Witness {
+    Wild,
+    Ctor(Head, Vec<Witness>),
+}
+
+impl Head {
+    fn arity(&self) -> usize {
+        match self {
+            Head::Int(_) | Head::Bool(_) => 0,
+            Head::Tuple(len) => *len,
+            Head::Data(ctor) => ctor.fields.len(),
+        }
+    }
+    /// How to reach the `index`-th component of a value that `self` matches.
+    fn step(&self, index: usize) -> Step {
+        match self {
+            Head::Data(ctor) => Step::Field(ctor.clone(), index),
+            _ => Step::Proj(index),
+        }
+    }
+    fn same(&self, other: &Head) -> bool {
+        match (self, other) {
+            (Head::Int(x), Head::Int(y)) => x == y,
+            (Head::Bool(x), Head::Bool(y)) => x == y,
+            (Head::Tuple(x), Head::Tuple(y)) => x == y,
+            (Head::Data(x), Head::Data(y)) => Rc::ptr_eq(x, y),
+            _ => false,
+        }
+    }
+    /// Whether the values the two heads match are of the same type.
+    fn same_type(&self, other: &Head) -> bool {
+        match (self, other) {
+            (Head::Int(_), Head::Int(_)) | (Head::Bool(_), Head::Bool(_)) => true,
+            (Head::Tuple(x), Head::Tuple(y)) => x == y,
+            (Head::Data(x), Head::Data(y)) => x.adt == y.adt,
+            _ => false,
+        }
+    }
+}
+
+impl Pat {
+    fn wild(span: Span) -> Pat {
+        Pat {
+            head: None,
+            args: Vec::new(),
+            span,
+        }
+    }
+}
+
+impl Context {
+    /// Resolves the constructors in `pattern`, which is matched against the part of the value at `path`,
+    /// and appends the names it binds to `bindings`.
+    pub(super) fn resolve_pattern(
+        &self,
+        pattern: ast::Pattern,
+        path: &mut Vec<Step>,
+        bindings: &mut Vec<Binding>,
+    ) -> Result<Pat, TranslateError> {
+        let span = pattern.span;
+        let (head, args) = match pattern.kind {
+            ast::PatternKind::Wildcard => return Ok(Pat::wild(span)),
+            ast::PatternKind::Var(name) => match self.ctors.get(&name) {
+                Some(ctor) => (Head::Data(ctor.clone()), Vec::new()),
+                None => {
+                    if bindings.iter().any(|binding| binding.name == name) {
+                        return Err(TranslateError::DuplicateBinding(name, span));
+                    }
+                    bindings.push(Binding {
+                        name,
+                        path: path.clone(),
+                        span,
+                    });
+                    return Ok(Pat::wild(span));
+                }
+            },
+            ast::PatternKind::Int(value) => (Head::Int(value), Vec::new()),
+            ast::PatternKind::Bool(value) => (Head::Bool(value), Vec::new()),
+            ast::PatternKind::Tuple(items) => (Head::Tuple(items.len()), items),
+            ast::PatternKind::Ctor(name, args) => match self.ctors.get(&name) {
+                Some(ctor) => (Head::Data(ctor.clone()), args),
+                None => return Err(TranslateError::UnknownConstructor(name, span)),
+            },
+        };
+        if let Head::Data(ref ctor) = head {
+            if args.len() != ctor.fields.len() {
+                return Err(TranslateError::ConstructorArity {
+                    name: ctor.name.clone(),
+                    expected: ctor.fields.len(),
+                    found: args.len(),
+                    span,
+                });
+            }
+        }
+        let args = args
+            .into_iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                path.push(head.step(index));
+                let arg = self.resolve_pattern(arg, path, bindings);
+                path.pop();
+                arg
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Pat {
+            head: Some(head),
+            args,
+            span,
+        })
+    }
+    /// Reports a value that none of `pats` matches, if any.
+    pub(super) fn check_exhaustive(&self, pats: &[Pat], span: Span) -> Result<(), TranslateError> {
+        let rows = pats.iter().map(|pat| vec![pat.clone()]).collect();
+        match self.useful(rows, vec![Pat::wild(span)])? {
+            Some(witness) => Err(TranslateError::NonExhaustive {
+                witness: witness[0].to_string(),
+                span,
+            }),
+            None => Ok(()),
+        }
+    }
+    /// Finds values that `q` matches and none of `rows` does, following Maranget's usefulness algorithm,
+    /// and returns one of them component by component.
+    fn useful(
+        &self,
+        rows: Vec<Vec<Pat>>,
+        q: Vec<Pat>,
+    ) -> Result<Option<Vec<Witness>>, TranslateError> {
+        let Some((first, rest)) = q.split_first() else {
+            return Ok(rows.is_empty().then(Vec::new));
+        };
+        if let Some(ref head) = first.head {
+            let q = first.args.iter().chain(rest).cloned().collect();
+            let witness = self.useful(specialize(&rows, head), q)?;
+            return Ok(witness.map(|witness| wrap(head, witness)));
+        }
+        let heads: Vec<_> = rows
+            .iter()
+            .filter_map(|row| Some((row[0].head.as_ref()?, row[0].span)))
+            .collect();
+        if let Some(&(head, span)) = heads.first() {
+            if let Some(&(_, other)) = heads.iter().find(|(other, _)| !head.same_type(other)) {
+                return Err(TranslateError::MismatchedPatterns {
+                    span: other,
+                    first: span,
+                });
+            }
+        }
+        match self.missing_head(&heads) {
+            None => {
+                // Every kind of value is covered by some row, so try each of them in turn.
+                for head in self.all_heads(heads[0].0) {
+                    let q = iter::repeat_with(|| Pat::wild(first.span))
+                        .take(head.arity())
+                        .chain(rest.iter().cloned())
+                        .collect();
+                    if let Some(witness) = self.useful(specialize(&rows, &head), q)? {
+                        return Ok(Some(wrap(&head, witness)));
+                    }
+                }
+                Ok(None)
+            }
+            Some(missing) => {
+                let rows = rows
+                    .into_iter()
+                    .filter(|row| row[0].head.is_none())
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let witness = self.useful(rows, rest.to_vec())?;
+                Ok(witness.map(|witness| iter::once(missing).chain(witness).collect()))
+            }
+        }
+    }
+    /// A value of the type that none of `heads` matches, or `None` if they cover every value.
+    fn missing_head(&self, heads: &[(&Head, Span)]) -> Option<Witness> {
+        let Some(&(head, _)) = heads.first() else {
+            return Some(Witness::Wild);
+        };
+        let all = self.all_heads(head);
+        if all.is_empty() {
+            // There are too many integers to list, so the smallest missing non-negative one stands for them.
+            let missing = (0..)
+                .find(|&value| !heads.iter().any(|(head, _)| head.same(&Head::Int(value))))
+                .expect("finitely many patterns cannot cover every integer");
+            return Some(Witness::Ctor(Head::Int(missing), Vec::new()));
+        }
+        let missing = all
+            .into_iter()
+            .find(|head| !heads.iter().any(|(other, _)| head.same(other)))?;
+        let args = iter::repeat_with(|| Witness::Wild)
+            .take(missing.arity())
+            .collect();
+        Some(Witness::Ctor(missing, args))
+    }
+    /// Every head of the type that `head` matches, which is empty for `Int`.
+    fn all_heads(&self, head: &Head) -> Vec<Head> {
+        match head {
+            Head::Int(_) => Vec::new(),
+            Head::Bool(_) => vec![Head::Bool(true), Head::Bool(false)],
+            Head::Tuple(len) => vec![Head::Tuple(*len)],
+            Head::Data(ctor) => self.adts[ctor.adt]
+                .iter()
+                .map(|ctor| Head::Data(ctor.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// The rows of `rows` that may match a value with `head` outermost, with the first column replaced by its components.
+fn specialize(rows: &[Vec<Pat>], head: &Head) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let (first, rest) = row.split_first()?;
+            let args = match first.head {
+                Some(ref other) if other.same(head) => first.args.clone(),
+                Some(_) => return None,
+                None => vec![Pat::wild(first.span); head.arity()],
+            };
+            Some(args.into_iter().chain(rest.iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// Puts the first components of `witness` back together under `head`.
+fn wrap(head: &Head, mut witness: Vec<Witness>) -> Vec<Witness> {
+    let rest = witness.split_off(head.arity());
+    iter::once(Witness::Ctor(head.clone(), witness))
+        .chain(rest)
+        .collect()
+}
+
+/// The part at `path` of the hidden variable of type `temp_ty`, which is `depth` scopes out.
+fn path_expr(
+    tys: &ir::TyTable,
+    path: &[Step],
+    temp_ty: &ir::Ty,
+    depth: usize,
+    span: Span,
+) -> ir::Expr {
+    let temp = ir::Expr {
+        kind: ir::ExprKind::Local {
+            depth,
+            index: 0,
+            ty: temp_ty.clone(),
+        },
+        span,
+    };
+    path.iter().fold(deref(tys, temp, span), |value, step| {
+        let func = match step {
+            Step::Proj(index) => ir::Func::Proj(*index),
+            Step::Field(ctor, index) => ir::Func::Field(ctor.clone(), *index),
+        };
+        call(func, vec![value], span)
+    })
+}
+
+/// The condition on which `pat` matches the part at `path` of the hidden variable, or `None` if it always does.
+/// A field is taken only after its constructor is tested, because `&&` skips the rest once a test fails,
+/// even when it is lifted over a scrutinee that is a function.
+pub(super) fn test(
+    tys: &ir::TyTable,
+    pat: &Pat,
+    path: &mut Vec<Step>,
+    temp_ty: &ir::Ty,
+) -> Option<ir::Expr> {
+    let span = pat.span;
+    let head = pat.head.as_ref()?;
+    let value = path_expr(tys, path, temp_ty, 0, span);
+    let mut tests = Vec::new();
+    match head {
+        Head::Int(int) => {
+            let int = ir::Expr {
+                kind: ir::ExprKind::Int(*int),
+                span,
+            };
+            tests.push(call(ir::Func::Eq, vec![value, int], span));
+        }
+        Head::Bool(true) => tests.push(value),
+        Head::Bool(false) => tests.push(call(ir::Func::Not, vec![value], span)),
+        Head::Tuple(_) => {}
+        Head::Data(ctor) => tests.push(call(ir::Func::Is(ctor.clone()), vec![value], span)),
+    }
+    for (index, arg) in pat.args.iter().enumerate() {
+        path.push(head.step(index));
+        tests.extend(test(tys, arg, path, temp_ty));
+        path.pop();
+    }
+    tests
+        .into_iter()
+        .reduce(|a, b| call(ir::Func::And, vec![a, b], span))
+}
+
+fn call(func: ir::Func, args: Vec<ir::Expr>, span: Span) -> ir::Expr {
+    ir::Expr {
+        kind: ir::ExprKind::Func {
+            func,
+            calls: vec![ir::Call::new(args, span)],
+        },
+        span,
+    }
+}
+
+impl Binding {
+    /// Assigns the bound part of the hidden variable, which is `depth` scopes out,
+    /// to the `index`-th local variable of the innermost scope, which has type `ty`.
+    pub(super) fn assign(
+        &self,
+        tys: &ir::TyTable,
+        index: usize,
+        ty: &ir::Ty,
+        temp_ty: &ir::Ty,
+        depth: usize,
+    ) -> ir::Expr {
+        let local = ir::Expr {
+            kind: ir::ExprKind::Local {
+                depth: 0,
+                index,
+                ty: ty.clone(),
+            },
+            span: self.span,
+        };
+        assign(
+            tys,
+            local,
+            path_expr(tys, &self.path, temp_ty, depth, self.span),
+            self.span,
+        )
+    }
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Display for Witness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (head, args) = match self {
+            Witness::Wild => return write!(f, "_"),
+            Witness::Ctor(head, args) => (head, args),
+        };
+        let args = args
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        match head {
+            Head::Int(value) => write!(f, "{value}"),
+            Head::Bool(value) => write!(f, "{value}"),
+            Head::Tuple(_) => write!(f, "({args})"),
+            Head::Data(ctor) if ctor.fields.is_empty() => write!(f, "{}", ctor.name),
+            Head::Data(ctor) => write!(f, "{}({args})", ctor.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    const TYPES: &str = "\
+type Shape = Circle(Int) | Rect(Int, Int)
+type Tree = Leaf | Node(Tree, Int, Tree)
+";
+
+    /// Translates `match VALUE { ARMS }` for `value` of type `ty`,
+    /// giving the value that the exhaustiveness check reports as not covered, if any.
+    fn witness(ty: &str, arms: &str) -> Option<String> {
+        let source = format!("{TYPES}f = (value: {ty}) => match value {{ {arms} }}");
+        let (statements, errors) = parser::parse(&source, 0);
+        assert!(errors.is_empty(), "`{source}` has a syntax error");
+        let mut context = Context::new(&ir::TyTable::new());
+        for statement in statements {
+            match context.translate_stmt(statement) {
+                Ok(_) => {}
+                Err(TranslateError::NonExhaustive { witness, .. }) => return Some(witness),
+                Err(err) => panic!("{}", err.to_diagnostic().message),
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn witnesses() {
+        let witness = |ty, arms| witness(ty, arms).unwrap();
+        assert_eq!(witness("Shape", "Circle(r) => r"), "Rect(_, _)");
+        assert_eq!(
+            witness("Shape", "Circle(r) => r, Rect(0, h) => h"),
+            "Rect(1, _)"
+        );
+        assert_eq!(
+            witness("(Bool, Bool)", "(true, _) => 1, (_, true) => 2"),
+            "(false, false)"
+        );
+        assert_eq!(witness("Int", "0 => 1, 1 => 2"), "2");
+    }
+
+    #[test]
+    fn nested_constructors() {
+        assert_eq!(
+            witness("Tree", "Leaf => 0, Node(Leaf, x, _) => x").as_deref(),
+            Some("Node(Node(_, _, _), _, _)")
+        );
+        assert_eq!(
+            witness(
+                "Tree",
+                "Leaf => 0, Node(Leaf, x, _) => x, Node(Node(_, y, _), _, _) => y"
+            ),
+            None
+        );
+        assert_eq!(
+            witness(
+                "Shape",
+                "Circle(1) => 1, Rect(x, y) => x + y, Circle(_) => 3"
+            ),
+            None
+        );
+        assert_eq!(
+            witness(
+                "(Shape, Bool)",
+                "(Circle(_), true) => 1, (Rect(_, _), _) => 2"
+            )
+            .as_deref(),
+            Some("(Circle(_), false)")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::span::Span;
+use std::fmt::Write;
+
+/// The number of columns a tab is shown as.
+const TAB_WIDTH: usize = 4;
+
+/// An error attached to a span of the source.
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    /// Printed next to the underline.
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            span,
+            label: None,
+            notes: Vec::new(),
+        }
+    }
+    pub fn with_label(mut self, label: impl Into<String>) -> Diagnostic {
+        self.label = Some(label.into());
+        self
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+    /// Renders `self` in the style of rustc, quoting the offending line of `source`, which was read from `name`.
+    /// A span over several lines is underlined up to the end of its first line, and tabs are shown as 4 spaces.
+    pub fn render(&self, name: &str, source: &str) -> String {
+        let mut ret = String::new();
+        let start = self.span.start;
+        let line_number = (start.line + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+        writeln!(ret, "error: {}", self.message).unwrap();
+        writeln!(ret, "{gutter}--> {name}:{start}").unwrap();
+        if let Some(line) = source.lines().nth(start.line) {
+            let line_len = line.chars().count();
+            let end_column = if self.span.end.line == start.line {
+                self.span.end.column
+            } else {
+                line_len
+            };
+            let width = |columns: usize| {
+                line.chars()
+                    .take(columns)
+                    .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+                    .sum::<usize>()
+            };
+            let (start_width, end_width) = (width(start.column), width(end_column));
+            writeln!(ret, "{gutter} |").unwrap();
+            writeln!(
+                ret,
+                "{line_number} | {}",
+                line.replace('\t', &" ".repeat(TAB_WIDTH))
+            )
+            .unwrap();
+            write!(
+                ret,
+                "{gutter} | {}{}",
+                " ".repeat(start_width),
+                "^".repeat(end_width.saturating_sub(start_width).max(1))
+            )
+            .unwrap();
+            if let Some(ref label) = self.label {
+                write!(ret, " {label}").unwrap();
+            }
+            writeln!(ret).unwrap();
+        }
+        if !self.notes.is_empty() {
+            writeln!(ret, "{gutter} |").unwrap();
+        }
+        for note in &self.notes {
+            writeln!(ret, "{gutter} = note: {note}").unwrap();
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Pos;
+
+    fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+        let pos = |(line, column)| Pos { line, column };
+        Span::new(pos(start), pos(end))
+    }
+
+    #[test]
+    fn columns() {
+        let source = "x = 1\ny = x + true\n";
+        let diagnostic = Diagnostic::error("mismatched types", span((1, 8), (1, 12)))
+            .with_label("expected `Int`")
+            .with_note("`Bool` is not `Int`");
+        assert_eq!(
+            diagnostic.render("test", source),
+            "error: mismatched types\n \
+             --> test:2:9\n  \
+             |\n\
+             2 | y = x + true\n  \
+             |         ^^^^ expected `Int`\n  \
+             |\n  \
+             = note: `Bool` is not `Int`\n"
+        );
+    }
+
+    #[test]
+    fn empty_spans_are_underlined_by_one_column() {
+        let diagnostic = Diagnostic::error("missing right hand side", span((0, 3), (0, 3)));
+        assert_eq!(
+            diagnostic.render("test", "x ="),
+            "error: missing right hand side\n --> test:1:4\n  |\n1 | x =\n  |    ^\n"
+        );
+    }
+
+    #[test]
+    fn tabs() {
+        let diagnostic = Diagnostic::error("unknown variable", span((0, 5), (0, 6)));
+        assert_eq!(
+            diagnostic.render("test", "x =\t\ty"),
+            "error: unknown variable\n --> test:1:6\n  |\n1 | x =        y\n  |            ^\n"
+        );
+    }
+
+    #[test]
+    fn multi_line_spans() {
+        let source = "f = {\n  x = 1\n}\n";
+        let diagnostic = Diagnostic::error("mismatched types", span((0, 4), (2, 1)));
+        assert_eq!(
+            diagnostic.render("test", source),
+            "error: mismatched types\n --> test:1:5\n  |\n1 | f = {\n  |     ^\n"
+        );
+        let diagnostic = Diagnostic::error("mismatched types", span((0, 0), (1, 3)));
+        assert_eq!(
+            diagnostic.render("test", source),
+            "error: mismatched types\n --> test:1:1\n  |\n1 | f = {\n  | ^^^^^\n"
+        );
+    }
+}
@@ -1,10 +1,51 @@
+mod assigned;
+mod derivation;
 mod fmt;
-use std::{cell::RefCell, collections::VecDeque, iter, rc::Rc};
+mod subtype;
+pub use derivation::Proof;
+use subtype::{subsume, subtype, Derivation, Rule};
+use crate::span::Span;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::VecDeque,
+    iter,
+    rc::{Rc, Weak},
+};
 
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+pub enum ExprKind {
     Int(i32),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<str>),
     Var(usize),
-    Func { func: Func, calls: Vec<Call> },
+    /// The `index`-th variable of the scope `depth` levels out from the innermost one, whose value has type `ty`.
+    Local {
+        depth: usize,
+        index: usize,
+        ty: Ty,
+    },
+    Lambda {
+        params: Vec<Ty>,
+        body: Rc<Expr>,
+    },
+    /// The components are evaluated and kept as they are, so a tuple is never lifted over them:
+    /// `([Int], 1)` is a pair of a function and an `Int`, not a function returning a pair.
+    Tuple(Vec<Expr>),
+    /// Evaluates `body` in a new scope with a variable of each type in `locals`, and returns the last value.
+    Block {
+        locals: Vec<Ty>,
+        body: Vec<Expr>,
+    },
+    Func {
+        func: Func,
+        calls: Vec<Call>,
+    },
 }
 
 pub enum Func {
@@ -16,15 +57,154 @@ pub enum Func {
     Mul,
     Div,
     Rem,
+    Neg,
+    /// The conversion from `Int` to `Float`, inserted by `check` where `Int <: Float` is used.
+    ToFloat,
+    /// The conversion to a function that takes arguments of the types and ignores them,
+    /// inserted by `check` where `T <: (S_0 ... S_m) T` is used.
+    ToConst(Vec<Ty>),
+    /// The conversion of a function to a supertype of its type by the derivation, which ends with S-Arrow,
+    /// inserted by `check` where the arguments or the result of a function are converted.
+    Convert(Rc<Derivation>),
+    /// The number of characters in a string.
+    Len,
+    /// The decimal representation of a number, as `str(42)`.
+    ToStr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
+    /// `if` whose branches have type `Ty`.
+    If(Ty),
+    /// The component at the index of a tuple. Like the other functions, it lifts over its argument,
+    /// so `[(Int, Int)].0` is a function returning the first component of its argument.
+    Proj(usize),
+    /// The identity on tuples of as many components, which `a, b = value` passes its value through
+    /// so that a value of another shape is reported as such. Like a projection, it lifts over its argument.
+    Unpack(usize),
+    /// Builds a value of a type declared with `type` from its fields.
+    Ctor(Rc<Ctor>),
+    /// Whether a value was built by the constructor, used by `match`.
+    Is(Rc<Ctor>),
+    /// The field at the index of a value built by the constructor, used by `match`.
+    Field(Rc<Ctor>, usize),
+}
+
+/// A constructor of a type declared with `type`.
+pub struct Ctor {
+    pub name: String,
+    /// The position of the constructor in the declaration, which tells the values it builds from the others.
+    pub tag: usize,
+    /// The `id` of the declared type.
+    pub adt: usize,
+    pub fields: Vec<Ty>,
+    /// The declared type, which is `TyInner::Named`.
+    pub ty: Ty,
 }
 
 pub struct Call {
     pub args: Vec<Expr>,
+    pub span: Span,
+    /// How many parameters were prepended to each argument by implicit lifting, filled in by `check`.
+    lifts: Vec<usize>,
+    /// The largest of `lifts`.
+    depth: usize,
+}
+
+impl Call {
+    pub fn new(args: Vec<Expr>, span: Span) -> Call {
+        Call {
+            args,
+            span,
+            lifts: Vec::new(),
+            depth: 0,
+        }
+    }
+}
+
+pub enum TypeError {
+    /// The argument at `position` has type `found`, which does not unify with the parameter type `expected`.
+    Mismatch {
+        expected: Ty,
+        found: Ty,
+        position: usize,
+        span: Span,
+    },
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    NotAFunction {
+        ty: Ty,
+        span: Span,
+    },
+    /// Unifying the argument at `position` would bind the undetermined type `var` to `ty`, which contains it.
+    InfiniteType {
+        var: Ty,
+        ty: Ty,
+        position: usize,
+        span: Span,
+    },
+    /// `.index` was applied to a value of type `ty`, which is not a tuple with that many components.
+    NoComponent {
+        ty: Ty,
+        index: usize,
+        span: Span,
+    },
+    /// A value of type `ty`, which is not a tuple of `expected` components, was assigned to a pattern of that many.
+    ComponentCount {
+        ty: Ty,
+        expected: usize,
+        span: Span,
+    },
+}
+
+/// Why two types do not unify.
+enum UnifyError {
+    Mismatch,
+    /// `var` would have to be `ty`, which contains it.
+    Infinite { var: Ty, ty: Ty },
+}
+
+/// The local variables of the enclosing lambdas and blocks, innermost first.
+pub type Env = Option<Rc<Frame>>;
+
+pub struct Frame {
+    vars: Vec<Value>,
+    parent: Env,
+}
+
+impl Frame {
+    fn get(&self, depth: usize, index: usize) -> &Value {
+        match depth {
+            0 => &self.vars[index],
+            _ => self
+                .parent
+                .as_ref()
+                .expect("scope out of range")
+                .get(depth - 1, index),
+        }
+    }
+}
+
+pub struct Closure {
+    params: Vec<Ty>,
+    body: Rc<Expr>,
+    env: Env,
 }
 
 #[derive(Clone)]
 pub enum Value {
     Int(i32),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<str>),
     Var(Rc<RefCell<Option<Value>>>),
     Id,
     Add,
@@ -32,204 +212,1179 @@ pub enum Value {
     Mul,
     Div,
     Rem,
+    Neg,
+    ToFloat,
+    ToConst,
+    Convert(Rc<Derivation>),
+    Len,
+    ToStr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
+    If,
+    Proj(usize),
+    Tuple(Vec<Value>),
+    Ctor(Rc<Ctor>),
+    Is(Rc<Ctor>),
+    Field(Rc<Ctor>, usize),
+    /// A value built by the constructor from the fields.
+    Data(Rc<Ctor>, Vec<Value>),
     Assign,
     Deref,
     Curry,
     App(Box<Value>, Vec<Value>),
     Const(Box<Value>),
+    /// A function converted by a derivation ending with S-Arrow, which converts the arguments and the result.
+    Converted(Box<Value>, Rc<Derivation>),
+    /// `if`, `&&` or `||` lifted over `depth` levels of calls, with the arguments of the calls so far.
+    /// Once every level is called, the condition is called with the arguments, and then only the operands that are needed.
+    Lifted {
+        func: Box<Value>,
+        depth: usize,
+        operands: Vec<Value>,
+        args: Vec<Vec<Value>>,
+    },
+    Closure(Rc<Closure>),
+}
+
+pub enum RuntimeError {
+    DivisionByZero,
+    Overflow,
+    /// A variable was read before anything was assigned to it.
+    Uninitialized,
+    /// A function was called with arguments of the wrong number or shape.
+    InvalidArguments,
+    NotAFunction,
 }
 
 impl Value {
-    fn call(&self, args: &[Value]) -> Value {
+    /// `vars` are the global variables.
+    fn call(&self, args: &[Value], vars: &[Value]) -> Result<Value, RuntimeError> {
         match self {
-            Value::Id => args[0].clone(),
-            Value::Add => match (&args[0], &args[1]) {
-                (&Value::Int(x), &Value::Int(y)) => Value::Int(x + y),
-                _ => panic!(),
+            Value::Id => match args {
+                [arg] => Ok(arg.clone()),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Add => match args {
+                [Value::Str(x), Value::Str(y)] => Ok(Value::Str(format!("{x}{y}").into())),
+                _ => arithmetic(args, i32::checked_add, |x, y| x + y),
+            },
+            Value::Sub => arithmetic(args, i32::checked_sub, |x, y| x - y),
+            Value::Mul => arithmetic(args, i32::checked_mul, |x, y| x * y),
+            Value::Div => match args {
+                [_, Value::Int(0)] => Err(RuntimeError::DivisionByZero),
+                _ => arithmetic(args, i32::checked_div, |x, y| x / y),
+            },
+            Value::Rem => match args {
+                [_, Value::Int(0)] => Err(RuntimeError::DivisionByZero),
+                _ => arithmetic(args, i32::checked_rem, |x, y| x % y),
+            },
+            Value::Neg => match *args {
+                [Value::Int(x)] => x.checked_neg().map(Value::Int).ok_or(RuntimeError::Overflow),
+                [Value::Float(x)] => Ok(Value::Float(-x)),
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Sub => match (&args[0], &args[1]) {
-                (&Value::Int(x), &Value::Int(y)) => Value::Int(x - y),
-                _ => panic!(),
+            Value::ToFloat => match *args {
+                [Value::Int(x)] => Ok(Value::Float(x.into())),
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Mul => match (&args[0], &args[1]) {
-                (&Value::Int(x), &Value::Int(y)) => Value::Int(x * y),
-                _ => panic!(),
+            Value::ToConst => match args {
+                [value] => Ok(Value::Const(Box::new(value.clone()))),
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Div => match (&args[0], &args[1]) {
-                (&Value::Int(x), &Value::Int(y)) => Value::Int(x / y),
-                _ => panic!(),
+            Value::Convert(derivation) => match args {
+                [value] => convert(derivation, value.clone()),
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Rem => match (&args[0], &args[1]) {
-                (&Value::Int(x), &Value::Int(y)) => Value::Int(x % y),
-                _ => panic!(),
+            Value::Len => match args {
+                [Value::Str(x)] => i32::try_from(x.chars().count())
+                    .map(Value::Int)
+                    .map_err(|_| RuntimeError::Overflow),
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Assign => match args[0] {
-                Value::Var(ref var) => {
-                    *var.borrow_mut() = Some(args[1].clone());
-                    Value::Var(var.clone())
+            Value::ToStr => match *args {
+                [Value::Int(x)] => Ok(Value::Str(x.to_string().into())),
+                [Value::Float(x)] => Ok(Value::Str(format!("{x:?}").into())),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Eq => comparison(args, |ordering| ordering == Some(Ordering::Equal)),
+            Value::Ne => comparison(args, |ordering| ordering != Some(Ordering::Equal)),
+            Value::Lt => comparison(args, |ordering| ordering == Some(Ordering::Less)),
+            Value::Le => comparison(args, |ordering| {
+                matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+            }),
+            Value::Gt => comparison(args, |ordering| ordering == Some(Ordering::Greater)),
+            Value::Ge => comparison(args, |ordering| {
+                matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+            }),
+            Value::And => match *args {
+                [Value::Bool(x), Value::Bool(y)] => Ok(Value::Bool(x && y)),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Or => match *args {
+                [Value::Bool(x), Value::Bool(y)] => Ok(Value::Bool(x || y)),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Not => match *args {
+                [Value::Bool(x)] => Ok(Value::Bool(!x)),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::If => match args {
+                [Value::Bool(cond), a, b] => Ok(if *cond { a.clone() } else { b.clone() }),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Proj(index) => match args {
+                [Value::Tuple(items)] if *index < items.len() => Ok(items[*index].clone()),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Ctor(ctor) => {
+                if args.len() != ctor.fields.len() {
+                    return Err(RuntimeError::InvalidArguments);
                 }
-                _ => panic!(),
+                Ok(Value::Data(ctor.clone(), args.to_vec()))
+            }
+            Value::Is(ctor) => match args {
+                [Value::Data(built_by, _)] => Ok(Value::Bool(built_by.tag == ctor.tag)),
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Deref => match args[0] {
-                Value::Var(ref var) => var.borrow().clone().expect(""),
-                _ => panic!(),
+            Value::Field(ctor, index) => match args {
+                [Value::Data(built_by, fields)] if built_by.tag == ctor.tag => {
+                    Ok(fields[*index].clone())
+                }
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Assign => match args {
+                [Value::Var(var), value] => {
+                    *var.borrow_mut() = Some(value.clone());
+                    Ok(Value::Var(var.clone()))
+                }
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Deref => match args {
+                [Value::Var(var)] => var.borrow().clone().ok_or(RuntimeError::Uninitialized),
+                _ => Err(RuntimeError::InvalidArguments),
+            },
+            Value::Curry => match args {
+                [func, converters @ ..] => {
+                    Ok(Value::App(Box::new(func.clone()), converters.to_vec()))
+                }
+                _ => Err(RuntimeError::InvalidArguments),
             },
-            Value::Curry => Value::App(Box::new(args[0].clone()), args[1..].to_vec()),
             Value::App(func, converters) => {
-                let converted_args: Vec<_> = converters
+                let converted_args = converters
                     .iter()
-                    .map(|converter| converter.call(args))
-                    .collect();
-                func.call(&converted_args)
+                    .map(|converter| converter.call(args, vars))
+                    .collect::<Result<Vec<_>, _>>()?;
+                func.call(&converted_args, vars)
+            }
+            Value::Const(value) => Ok(*value.clone()),
+            Value::Converted(func, derivation) => {
+                let (ret, params) = derivation
+                    .premises
+                    .split_first()
+                    .expect("S-Arrow must relate the results");
+                if args.len() != params.len() {
+                    return Err(RuntimeError::InvalidArguments);
+                }
+                let converted_args = params
+                    .iter()
+                    .zip(args)
+                    .map(|(param, arg)| convert(param, arg.clone()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                convert(ret, func.call(&converted_args, vars)?)
+            }
+            Value::Lifted {
+                func,
+                depth,
+                operands,
+                args: prev_args,
+            } => {
+                let mut all_args = prev_args.clone();
+                all_args.push(args.to_vec());
+                if all_args.len() < *depth {
+                    return Ok(Value::Lifted {
+                        func: func.clone(),
+                        depth: *depth,
+                        operands: operands.clone(),
+                        args: all_args,
+                    });
+                }
+                eval_lazily(func, operands, |operand| {
+                    all_args
+                        .iter()
+                        .try_fold(operand.clone(), |value, args| value.call(args, vars))
+                })
+            }
+            Value::Closure(closure) => {
+                if args.len() != closure.params.len() {
+                    return Err(RuntimeError::InvalidArguments);
+                }
+                let frame = Frame {
+                    vars: args
+                        .iter()
+                        .map(|arg| Value::Var(Rc::new(RefCell::new(Some(arg.clone())))))
+                        .collect(),
+                    parent: closure.env.clone(),
+                };
+                closure.body.eval(vars, &Some(Rc::new(frame)))
+            }
+            Value::Int(_)
+            | Value::Float(_)
+            | Value::Bool(_)
+            | Value::Str(_)
+            | Value::Tuple(_)
+            | Value::Data(..)
+            | Value::Var(_) => {
+                Err(RuntimeError::NotAFunction)
             }
-            Value::Const(value) => *value.clone(),
-            _ => panic!("not a function"),
         }
     }
 }
 
+/// Converts `value` from `derivation.sub` to `derivation.sup` by the conversion for each step of the derivation.
+fn convert(derivation: &Rc<Derivation>, value: Value) -> Result<Value, RuntimeError> {
+    match derivation.rule {
+        Rule::Atom => Ok(value),
+        Rule::Int => Value::ToFloat.call(&[value], &[]),
+        Rule::Const => Ok(Value::Const(Box::new(convert(&derivation.premises[0], value)?))),
+        Rule::Arrow => Ok(Value::Converted(Box::new(value), derivation.clone())),
+    }
+}
+
+/// Applies `int_op` to two `Int`s or `float_op` to two `Float`s.
+fn arithmetic(
+    args: &[Value],
+    int_op: fn(i32, i32) -> Option<i32>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value, RuntimeError> {
+    match *args {
+        [Value::Int(x), Value::Int(y)] => int_op(x, y).map(Value::Int).ok_or(RuntimeError::Overflow),
+        [Value::Float(x), Value::Float(y)] => Ok(Value::Float(float_op(x, y))),
+        _ => Err(RuntimeError::InvalidArguments),
+    }
+}
+
+/// Compares two `Int`s, two `Float`s or two `Str`s, and tells the result to `op`, which gets `None` if either is NaN.
+/// Strings are ordered lexicographically by code point.
+fn comparison(args: &[Value], op: fn(Option<Ordering>) -> bool) -> Result<Value, RuntimeError> {
+    match args {
+        [Value::Int(x), Value::Int(y)] => Ok(Value::Bool(op(Some(x.cmp(y))))),
+        [Value::Float(x), Value::Float(y)] => Ok(Value::Bool(op(x.partial_cmp(y)))),
+        [Value::Str(x), Value::Str(y)] => Ok(Value::Bool(op(Some(x.cmp(y))))),
+        _ => Err(RuntimeError::InvalidArguments),
+    }
+}
+
 macro_rules! ty {
-    (Int) => {
-        Ty::new(TyInner::Int)
+    ($tys:expr; Int) => {
+        $tys.new_ty(TyInner::Int)
+    };
+    ($tys:expr; Float) => {
+        $tys.new_ty(TyInner::Float)
+    };
+    ($tys:expr; Bool) => {
+        $tys.new_ty(TyInner::Bool)
     };
-    (Ref $ty:expr) => {
-        Ty::new(TyInner::Ref($ty))
+    ($tys:expr; Str) => {
+        $tys.new_ty(TyInner::Str)
     };
-    (($($args:expr),*) $ret:expr) => {
-        Ty::new(TyInner::Func { args: vec![$($args),*], ret: $ret } )
+    ($tys:expr; Ref $ty:expr) => {
+        $tys.new_ty(TyInner::Ref($ty))
+    };
+    ($tys:expr; ($($args:expr),*) $ret:expr) => {
+        $tys.new_ty(TyInner::Func { args: vec![$($args),*], ret: $ret } )
     }
 }
 
 impl Expr {
-    pub fn eval(&self, vars: &[(Ty, Value)]) -> (Ty, Value) {
-        match *self {
-            Expr::Int(value) => (ty!(Int), Value::Int(value)),
-            Expr::Var(idx) => vars[idx].clone(),
-            Expr::Func {
+    /// Infers the type of `self`, and records where implicit lifting happens for `eval`.
+    /// `vars` are the types of the values held by the variables, and the types made go to `tys`.
+    pub fn check(&mut self, tys: &TyTable, vars: &[Ty]) -> Result<Ty, TypeError> {
+        Ok(self.infer(tys, vars, false)?.0)
+    }
+    /// Checks `self` as `check` does, and also returns the derivation of its type.
+    pub fn derive(&mut self, tys: &TyTable, vars: &[Ty]) -> Result<(Ty, Proof), TypeError> {
+        let (ty, proof) = self.infer(tys, vars, true)?;
+        Ok((ty, proof.expect("a derivation must be recorded")))
+    }
+    /// Does the work of `check`, recording the derivation of the type if `record` is set.
+    fn infer(&mut self, tys: &TyTable, vars: &[Ty], record: bool) -> Result<(Ty, Option<Proof>), TypeError> {
+        let leaf = |term: &Expr, ty: Ty| -> Result<_, TypeError> {
+            let proof = record.then(|| Proof::var(term.to_string(), ty.clone()));
+            Ok((ty, proof))
+        };
+        match self.kind {
+            ExprKind::Int(_) => leaf(self, ty!(tys; Int)),
+            ExprKind::Float(_) => leaf(self, ty!(tys; Float)),
+            ExprKind::Bool(_) => leaf(self, ty!(tys; Bool)),
+            ExprKind::Str(_) => leaf(self, ty!(tys; Str)),
+            ExprKind::Var(idx) => leaf(self, ty!(tys; Ref vars[idx].clone())),
+            ExprKind::Local { ref ty, .. } => leaf(self, ty!(tys; Ref ty.clone())),
+            ExprKind::Lambda {
+                ref params,
+                ref mut body,
+            } => {
+                let ret = Rc::get_mut(body)
+                    .expect("a lambda must be checked before it is evaluated")
+                    .check(tys, vars)?;
+                let ty = tys.new_ty(TyInner::Func {
+                    args: params.clone(),
+                    ret,
+                });
+                // The note has no rule for abstraction, so a lambda is a constant of its type as a built-in function is.
+                leaf(self, ty)
+            }
+            ExprKind::Tuple(ref mut items) => {
+                let (items_ty, items): (Vec<_>, Vec<_>) = items
+                    .iter_mut()
+                    .map(|item| item.infer(tys, vars, record))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .unzip();
+                let ty = tys.new_ty(TyInner::Tuple(items_ty.clone()));
+                // A tuple is the application of a constant that makes it from the items.
+                let proof = items.into_iter().collect::<Option<Vec<_>>>().map(|items| {
+                    let func_ty = tys.new_ty(TyInner::Func {
+                        args: items_ty,
+                        ret: ty.clone(),
+                    });
+                    let func = Proof::var("Tuple".to_string(), func_ty);
+                    Proof::app(func, items, ty.clone())
+                });
+                Ok((ty, proof))
+            }
+            ExprKind::Block { ref mut body, .. } => {
+                let mut exprs_ty = Vec::new();
+                let mut proofs = Vec::new();
+                for expr in body {
+                    let (expr_ty, proof) = expr.infer(tys, vars, record)?;
+                    exprs_ty.push(expr_ty);
+                    proofs.extend(proof);
+                }
+                let ty = exprs_ty.last().expect("a block must end with an expression").clone();
+                // A block is the application of a constant that gives the last of its arguments.
+                let proof = record.then(|| {
+                    let func_ty = tys.new_ty(TyInner::Func {
+                        args: exprs_ty,
+                        ret: ty.clone(),
+                    });
+                    let func = Proof::var("Block".to_string(), func_ty);
+                    Proof::app(func, proofs, ty.clone())
+                });
+                Ok((ty, proof))
+            }
+            ExprKind::Func {
                 ref func,
-                ref calls,
+                ref mut calls,
             } => {
-                let (mut ty, mut value) = match func {
-                    Func::Id(ty) => (ty!((ty.clone()) ty.clone()), Value::Id),
-                    Func::Add => (ty!((ty!(Int), ty!(Int)) ty!(Int)), Value::Add),
-                    Func::Sub => (ty!((ty!(Int), ty!(Int)) ty!(Int)), Value::Sub),
-                    Func::Mul => (ty!((ty!(Int), ty!(Int)) ty!(Int)), Value::Mul),
-                    Func::Div => (ty!((ty!(Int), ty!(Int)) ty!(Int)), Value::Div),
-                    Func::Rem => (ty!((ty!(Int), ty!(Int)) ty!(Int)), Value::Rem),
-                    Func::Assign(ty) => (
-                        ty!((ty!(Ref ty.clone()), ty.clone()) ty!(Ref ty.clone())),
-                        Value::Assign,
-                    ),
-                    Func::Deref(ty) => (ty!((ty!(Ref ty.clone())) ty.clone()), Value::Deref),
-                };
+                let mut ty = None;
+                let mut proof = None;
                 for call in calls {
-                    let (args_ty, ret_ty) = ty.get_args_ret();
-                    assert_eq!(call.args.len(), args_ty.len());
-                    let call_args: Vec<_> = args_ty
-                        .iter()
-                        .zip(&call.args)
-                        .map(|(arg_ty, call_arg)| {
-                            let (call_arg_ty, call_arg_value) = call_arg.eval(vars);
-                            let extra_calls = call_arg_ty.unify(arg_ty).expect("type error");
-                            (call_arg_value, extra_calls)
-                        })
-                        .collect();
-                    let max_extra_calls = match call_args
-                        .iter()
-                        .max_by_key(|(_, extra_calls)| extra_calls.len())
+                    let (call_args_ty, mut call_args_proof): (Vec<_>, Vec<_>) = call
+                        .args
+                        .iter_mut()
+                        .map(|arg| arg.infer(tys, vars, record))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .into_iter()
+                        .unzip();
+                    let ty = match ty {
+                        Some(ref mut ty) => ty,
+                        None => ty.insert(func.ty(tys, &call_args_ty).ok_or_else(|| match func {
+                            Func::Proj(index) => TypeError::NoComponent {
+                                ty: call_args_ty[0].clone(),
+                                index: *index,
+                                span: call.span,
+                            },
+                            Func::Unpack(len) => TypeError::ComponentCount {
+                                ty: call_args_ty[0].clone(),
+                                expected: *len,
+                                span: call.span,
+                            },
+                            _ => unreachable!("only a projection or an unpacking can have no type"),
+                        })?),
+                    };
+                    let func_proof = match proof.take() {
+                        Some(proof) => Some(proof),
+                        None => record.then(|| Proof::var(func.term(), ty.clone())),
+                    };
+                    let (args_ty, ret_ty) = ty.as_func(call.args.len()).ok_or_else(|| {
+                        TypeError::NotAFunction {
+                            ty: ty.clone(),
+                            span: call.span,
+                        }
+                    })?;
+                    if call.args.len() != args_ty.len() {
+                        return Err(TypeError::ArgumentCount {
+                            expected: args_ty.len(),
+                            found: call.args.len(),
+                            span: call.span,
+                        });
+                    }
+                    let mut extra_calls: Vec<VecDeque<Vec<Ty>>> = Vec::new();
+                    for (position, ((arg_ty, call_arg), mut call_arg_ty)) in
+                        args_ty.iter().zip(&mut call.args).zip(call_args_ty).enumerate()
                     {
-                        Some((_, extra_calls)) => extra_calls.clone(),
+                        if matches!(*arg_ty.atom().inner(), TyInner::Float)
+                            && matches!(*call_arg_ty.atom().inner(), TyInner::Int)
+                        {
+                            let int_ty = call_arg_ty;
+                            call_arg_ty = call_arg.coerce_to_float(int_ty.clone());
+                            call_args_proof[position] = call_args_proof[position].take().map(|proof| {
+                                let derivation = subtype(&int_ty, &call_arg_ty)
+                                    .expect("a conversion must follow `Int <: Float`");
+                                proof.coerce(&derivation)
+                            });
+                        }
+                        // A branch of an `if` whose condition is lifted is lifted alike if it can be,
+                        // rather than taken as a function value, so that `if [Int] > 0 then [Int] else 0` is a function of `Int`.
+                        let extra = match (func, extra_calls.first()) {
+                            (Func::If(_), Some(cond_extra)) if !cond_extra.is_empty() => {
+                                call_arg_ty.unify_lifted(arg_ty, cond_extra.len())
+                            }
+                            _ => call_arg_ty.unify(arg_ty),
+                        };
+                        let extra = extra.map_err(|err| match err {
+                            UnifyError::Mismatch => TypeError::Mismatch {
+                                expected: arg_ty.clone(),
+                                found: call_arg_ty.clone(),
+                                position,
+                                span: call_arg.span,
+                            },
+                            UnifyError::Infinite { var, ty } => TypeError::InfiniteType {
+                                var,
+                                ty,
+                                position,
+                                span: call_arg.span,
+                            },
+                        })?;
+                        if let Some(derivation) = call_arg.convert_arg(&call_arg_ty, arg_ty, &extra) {
+                            call_args_proof[position] =
+                                call_args_proof[position].take().map(|proof| proof.coerce(&derivation));
+                        }
+                        extra_calls.push(extra);
+                    }
+                    let max_extra_calls = match extra_calls.iter().max_by_key(|extra| extra.len()) {
+                        Some(extra_calls) => extra_calls.clone(),
                         None => VecDeque::new(),
                     };
-                    let mut args: Vec<Value> = call_args
-                        .into_iter()
-                        .map(|(arg, extra_calls)| {
-                            (extra_calls.len()..max_extra_calls.len())
-                                .fold(arg, |value, _| Value::Const(Box::new(value)))
-                        })
-                        .collect();
-                    for i in (0..max_extra_calls.len()).rev() {
+                    call.lifts = extra_calls.iter().map(VecDeque::len).collect();
+                    call.depth = max_extra_calls.len();
+                    *ty = max_extra_calls
+                        .iter()
+                        .cloned()
+                        .fold(ret_ty, |ret, args| tys.new_ty(TyInner::Func { args, ret }));
+                    if let Some(func_proof) = func_proof {
+                        // The function and the arguments lifted less than the others are lifted by S-Const,
+                        // an argument ignoring the outer parameters as `Const` does in `eval`.
+                        let lift = |proof: Proof, lifts: usize| {
+                            max_extra_calls
+                                .iter()
+                                .skip(lifts)
+                                .fold(proof, |proof, params| proof.lift(params.clone()))
+                        };
+                        let args = call_args_proof
+                            .into_iter()
+                            .zip(&call.lifts)
+                            .map(|(proof, &lifts)| lift(proof.expect("a derivation must be recorded"), lifts))
+                            .collect();
+                        proof = Some(Proof::app(lift(func_proof, 0), args, ty.clone()));
+                    }
+                }
+                match ty {
+                    Some(ty) => Ok((ty, proof)),
+                    None => {
+                        let ty = func.ty(tys, &[]).expect("a projection must be called");
+                        let proof = record.then(|| Proof::var(func.term(), ty.clone()));
+                        Ok((ty, proof))
+                    }
+                }
+            }
+        }
+    }
+    /// Wraps `self`, which has type `ty` whose atom is `Int`, in a conversion to `Float` following `Int <: Float`.
+    /// The conversion lifts over functions, so a function returning `Int` becomes one returning `Float`.
+    /// Returns the type of the wrapped expression.
+    fn coerce_to_float(&mut self, ty: Ty) -> Ty {
+        let tys = ty.table();
+        let Ok(extra_calls) = ty.unify(&ty!(tys; Int)) else {
+            unreachable!("the atom of the type must be `Int`");
+        };
+        self.wrap(Func::ToFloat, extra_calls.len());
+        extra_calls
+            .into_iter()
+            .fold(ty!(tys; Float), |ret, args| tys.new_ty(TyInner::Func { args, ret }))
+    }
+    /// Converts `self`, an argument of type `ty` that `unify` has lifted over `extra_calls` to pass where `param` is,
+    /// to `param` past those levels, since it may be only a subtype of it, as by S-Arrow or S-Const.
+    /// Returns the derivation of the subtyping of `ty` and the type of the converted argument, if it is converted.
+    fn convert_arg(&mut self, ty: &Ty, param: &Ty, extra_calls: &VecDeque<Vec<Ty>>) -> Option<Derivation> {
+        let mut ret = ty.clone();
+        for _ in 0..extra_calls.len() {
+            ret = ret.ret().expect("a lifted level must be a call");
+        }
+        if ret.equals(param) {
+            return None;
+        }
+        let derivation = subtype(&ret, param).expect("`unify` must relate the argument to the parameter");
+        self.convert(&Rc::new(derivation), extra_calls.len());
+        let converted_ty = extra_calls
+            .iter()
+            .cloned()
+            .fold(param.clone(), |ret, args| ty.table().new_ty(TyInner::Func { args, ret }));
+        Some(subtype(ty, &converted_ty).expect("a conversion lifted over functions must follow S-Arrow"))
+    }
+    /// Wraps `self` in a conversion for each step of `derivation`, lifted over `depth` levels of parameters:
+    /// `ToFloat` for SA-Int, `ToConst` for SA-Const and `Convert` for SA-Arrow.
+    fn convert(&mut self, derivation: &Rc<Derivation>, depth: usize) {
+        let func = match derivation.rule {
+            Rule::Atom => return,
+            Rule::Int => Func::ToFloat,
+            Rule::Const => {
+                self.convert(&derivation.premises[0], depth);
+                let (params, _) = derivation.sup.get_args_ret().expect("SA-Const must give a function");
+                Func::ToConst(params)
+            }
+            Rule::Arrow => Func::Convert(derivation.clone()),
+        };
+        self.wrap(func, depth);
+    }
+    /// Wraps `self` in a call to `func`, lifted over `depth` levels of parameters.
+    fn wrap(&mut self, func: Func, depth: usize) {
+        let span = self.span;
+        let expr = std::mem::replace(
+            self,
+            Expr {
+                kind: ExprKind::Int(0),
+                span,
+            },
+        );
+        let mut call = Call::new(vec![expr], span);
+        call.lifts = vec![depth];
+        call.depth = depth;
+        *self = Expr {
+            kind: ExprKind::Func {
+                func,
+                calls: vec![call],
+            },
+            span,
+        };
+    }
+    /// Evaluates `self`, which must have been type-checked with `check`.
+    /// `vars` are the global variables and `env` the local ones.
+    pub fn eval(&self, vars: &[Value], env: &Env) -> Result<Value, RuntimeError> {
+        match self.kind {
+            ExprKind::Int(value) => Ok(Value::Int(value)),
+            ExprKind::Float(value) => Ok(Value::Float(value)),
+            ExprKind::Bool(value) => Ok(Value::Bool(value)),
+            ExprKind::Str(ref value) => Ok(Value::Str(value.clone())),
+            ExprKind::Var(idx) => Ok(vars[idx].clone()),
+            ExprKind::Local { depth, index, .. } => Ok(env
+                .as_ref()
+                .expect("a local variable outside of any scope")
+                .get(depth, index)
+                .clone()),
+            ExprKind::Lambda {
+                ref params,
+                ref body,
+            } => Ok(Value::Closure(Rc::new(Closure {
+                params: params.clone(),
+                body: body.clone(),
+                env: env.clone(),
+            }))),
+            ExprKind::Tuple(ref items) => Ok(Value::Tuple(
+                items
+                    .iter()
+                    .map(|item| item.eval(vars, env))
+                    .collect::<Result<_, _>>()?,
+            )),
+            ExprKind::Block {
+                ref locals,
+                ref body,
+            } => {
+                let frame = Frame {
+                    vars: locals
+                        .iter()
+                        .map(|_| Value::Var(Rc::new(RefCell::new(None))))
+                        .collect(),
+                    parent: env.clone(),
+                };
+                let env = Some(Rc::new(frame));
+                let mut value = None;
+                for expr in body {
+                    value = Some(expr.eval(vars, &env)?);
+                }
+                Ok(value.expect("a block must end with an expression"))
+            }
+            ExprKind::Func {
+                ref func,
+                ref calls,
+            } => {
+                let (mut value, calls) = match (func, &calls[..]) {
+                    // `if`, `&&` and `||` evaluate only the operands they need.
+                    (Func::If(_) | Func::And | Func::Or, [call, rest @ ..]) if call.depth == 0 => {
+                        let value = eval_lazily(&func.value(), &call.args, |arg| arg.eval(vars, env))?;
+                        (value, rest)
+                    }
+                    (Func::If(_) | Func::And | Func::Or, [call, rest @ ..]) => {
+                        let value = Value::Lifted {
+                            func: Box::new(func.value()),
+                            depth: call.depth,
+                            operands: call.eval_args(vars, env)?,
+                            args: Vec::new(),
+                        };
+                        (value, rest)
+                    }
+                    _ => (func.value(), &calls[..]),
+                };
+                for call in calls {
+                    let mut args = call.eval_args(vars, env)?;
+                    for i in (0..call.depth).rev() {
                         args = iter::once((0..i).fold(value, |v, _| Value::Const(Box::new(v))))
                             .chain(args)
                             .collect();
                         value = Value::Curry;
                     }
-                    ty = max_extra_calls
-                        .into_iter()
-                        .fold(ret_ty, |ret, args| Ty::new(TyInner::Func { args, ret }));
-                    value = value.call(&args);
+                    value = value.call(&args, vars)?;
                 }
-                (ty, value)
+                Ok(value)
             }
         }
     }
 }
 
+impl Func {
+    /// The type of `self` when it is first called with arguments of type `args`.
+    /// The arithmetic and comparisons are on `Float` if any argument is, and on `Int` otherwise,
+    /// except that `+` and comparisons are on `Str` if any argument is.
+    /// A projection takes the tuple that its argument is or returns, and is `None` if there is none with the component;
+    /// likewise an unpacking, if there is none with as many components.
+    fn ty(&self, tys: &TyTable, args: &[Ty]) -> Option<Ty> {
+        let numeric = if args
+            .iter()
+            .any(|arg| matches!(*arg.atom().inner(), TyInner::Float))
+        {
+            ty!(tys; Float)
+        } else {
+            ty!(tys; Int)
+        };
+        let operand = if args
+            .iter()
+            .any(|arg| matches!(*arg.atom().inner(), TyInner::Str))
+        {
+            ty!(tys; Str)
+        } else {
+            numeric.clone()
+        };
+        Some(match self {
+            Func::Id(ty) => ty!(tys; (ty.clone()) ty.clone()),
+            Func::Add => ty!(tys; (operand.clone(), operand.clone()) operand),
+            Func::Sub | Func::Mul | Func::Div | Func::Rem => {
+                ty!(tys; (numeric.clone(), numeric.clone()) numeric)
+            }
+            Func::Neg => ty!(tys; (numeric.clone()) numeric),
+            Func::ToFloat => ty!(tys; (ty!(tys; Int)) ty!(tys; Float)),
+            Func::ToConst(params) => {
+                let ty = args.first()?.clone();
+                ty!(tys; (ty.clone()) tys.new_ty(TyInner::Func { args: params.clone(), ret: ty }))
+            }
+            Func::Convert(derivation) => ty!(tys; (derivation.sub.clone()) derivation.sup.clone()),
+            Func::Len => ty!(tys; (ty!(tys; Str)) ty!(tys; Int)),
+            Func::ToStr => ty!(tys; (numeric) ty!(tys; Str)),
+            Func::Eq | Func::Ne | Func::Lt | Func::Le | Func::Gt | Func::Ge => {
+                ty!(tys; (operand.clone(), operand) ty!(tys; Bool))
+            }
+            Func::And | Func::Or => ty!(tys; (ty!(tys; Bool), ty!(tys; Bool)) ty!(tys; Bool)),
+            Func::Not => ty!(tys; (ty!(tys; Bool)) ty!(tys; Bool)),
+            Func::If(ty) => ty!(tys; (ty!(tys; Bool), ty.clone(), ty.clone()) ty.clone()),
+            Func::Assign(ty) => ty!(tys; (ty!(tys; Ref ty.clone()), ty.clone()) ty!(tys; Ref ty.clone())),
+            Func::Deref(ty) => ty!(tys; (ty!(tys; Ref ty.clone())) ty.clone()),
+            Func::Proj(index) => {
+                let tuple = args.first()?.atom();
+                let inner = tuple.inner();
+                let TyInner::Tuple(ref items) = *inner else {
+                    return None;
+                };
+                ty!(tys; (tuple.clone()) items.get(*index)?.clone())
+            }
+            Func::Unpack(len) => {
+                let tuple = args.first()?.atom();
+                match *tuple.inner() {
+                    TyInner::Tuple(ref items) if items.len() == *len => {}
+                    _ => return None,
+                }
+                ty!(tys; (tuple.clone()) tuple)
+            }
+            Func::Ctor(ctor) => tys.new_ty(TyInner::Func {
+                args: ctor.fields.clone(),
+                ret: ctor.ty.clone(),
+            }),
+            Func::Is(ctor) => ty!(tys; (ctor.ty.clone()) ty!(tys; Bool)),
+            Func::Field(ctor, index) => ty!(tys; (ctor.ty.clone()) ctor.fields[*index].clone()),
+        })
+    }
+    fn value(&self) -> Value {
+        match self {
+            Func::Id(_) => Value::Id,
+            Func::Add => Value::Add,
+            Func::Sub => Value::Sub,
+            Func::Mul => Value::Mul,
+            Func::Div => Value::Div,
+            Func::Rem => Value::Rem,
+            Func::Neg => Value::Neg,
+            Func::ToFloat => Value::ToFloat,
+            Func::ToConst(_) => Value::ToConst,
+            Func::Convert(derivation) => Value::Convert(derivation.clone()),
+            Func::Len => Value::Len,
+            Func::ToStr => Value::ToStr,
+            Func::Eq => Value::Eq,
+            Func::Ne => Value::Ne,
+            Func::Lt => Value::Lt,
+            Func::Le => Value::Le,
+            Func::Gt => Value::Gt,
+            Func::Ge => Value::Ge,
+            Func::And => Value::And,
+            Func::Or => Value::Or,
+            Func::Not => Value::Not,
+            Func::If(_) => Value::If,
+            Func::Proj(index) => Value::Proj(*index),
+            Func::Unpack(_) => Value::Id,
+            Func::Ctor(ctor) => Value::Ctor(ctor.clone()),
+            Func::Is(ctor) => Value::Is(ctor.clone()),
+            Func::Field(ctor, index) => Value::Field(ctor.clone(), *index),
+            Func::Assign(_) => Value::Assign,
+            Func::Deref(_) => Value::Deref,
+        }
+    }
+}
+
+impl Call {
+    /// Evaluates the arguments, each made a function of `depth` levels by ignoring the arguments of the levels it is not lifted over.
+    fn eval_args(&self, vars: &[Value], env: &Env) -> Result<Vec<Value>, RuntimeError> {
+        self.args
+            .iter()
+            .zip(&self.lifts)
+            .map(|(arg, &lift)| {
+                Ok((lift..self.depth)
+                    .fold(arg.eval(vars, env)?, |value, _| Value::Const(Box::new(value))))
+            })
+            .collect()
+    }
+}
+
+/// Applies `if`, `&&` or `||` to `operands`, evaluating with `eval` only the operands whose value is needed.
+fn eval_lazily<T>(
+    func: &Value,
+    operands: &[T],
+    eval: impl Fn(&T) -> Result<Value, RuntimeError>,
+) -> Result<Value, RuntimeError> {
+    let (cond, rest) = operands.split_first().ok_or(RuntimeError::InvalidArguments)?;
+    let Value::Bool(cond) = eval(cond)? else {
+        return Err(RuntimeError::InvalidArguments);
+    };
+    match (func, cond, rest) {
+        (Value::If, true, [a, _]) | (Value::If, false, [_, a]) => eval(a),
+        (Value::And, false, [_]) | (Value::Or, true, [_]) => Ok(Value::Bool(cond)),
+        (Value::And | Value::Or, _, [b]) => eval(b),
+        _ => Err(RuntimeError::InvalidArguments),
+    }
+}
+
+/// A type, as an index into the `TyTable` it was made in, which must outlive it.
+/// The table is a union-find structure whose classes are the types made equal by unification:
+/// only the representative of a class, found with path compression, holds the structure of the type.
 #[derive(Clone)]
 pub struct Ty {
-    inner: Rc<RefCell<TyInner>>,
+    /// The table, which the type does not keep alive, since the table holds types itself.
+    nodes: Weak<RefCell<TyNodes>>,
+    index: usize,
 }
-impl Ty {
-    pub fn new(inner: TyInner) -> Ty {
+
+#[derive(Clone)]
+struct TyNode {
+    parent: usize,
+    /// An upper bound on the height of the tree under a representative, which decides which of two classes goes under the other.
+    rank: u32,
+    /// The structure of the type, which is meaningful only for a representative.
+    /// It is shared with the callers of `Ty::inner`, so that they need not copy it.
+    inner: Rc<TyInner>,
+}
+
+struct TyNodes {
+    nodes: Vec<TyNode>,
+    /// The nodes as they were before each change since `checkpoint`, with their indices, for `rollback` to restore.
+    undo: Option<Vec<(usize, TyNode)>>,
+}
+
+impl TyNodes {
+    /// The node at `index`, to be changed.
+    fn node_mut(&mut self, index: usize) -> &mut TyNode {
+        if let Some(ref mut undo) = self.undo {
+            undo.push((index, self.nodes[index].clone()));
+        }
+        &mut self.nodes[index]
+    }
+}
+
+/// The types made for a session, which are freed with the last clone of it.
+#[derive(Clone)]
+pub struct TyTable(Rc<RefCell<TyNodes>>);
+
+thread_local! {
+    /// The structure of every undetermined type, which is most of the types made.
+    static UNDETERMINED: Rc<TyInner> = Rc::new(TyInner::Undetermined);
+}
+
+impl TyTable {
+    pub fn new() -> TyTable {
+        TyTable(Rc::new(RefCell::new(TyNodes {
+            nodes: Vec::new(),
+            undo: None,
+        })))
+    }
+    pub fn new_ty(&self, inner: TyInner) -> Ty {
+        let inner = match inner {
+            TyInner::Undetermined => UNDETERMINED.with(Rc::clone),
+            _ => Rc::new(inner),
+        };
+        let mut tys = self.0.borrow_mut();
+        let index = tys.nodes.len();
+        tys.nodes.push(TyNode {
+            parent: index,
+            rank: 0,
+            inner,
+        });
         Ty {
-            inner: Rc::new(RefCell::new(inner)),
+            nodes: Rc::downgrade(&self.0),
+            index,
+        }
+    }
+    /// The number of types made and not freed by `rollback`.
+    #[cfg(test)]
+    pub fn num_types(&self) -> usize {
+        self.0.borrow().nodes.len()
+    }
+    /// Starts recording the changes to the types, so that `rollback` can undo them.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let mut tys = self.0.borrow_mut();
+        assert!(tys.undo.is_none(), "checkpoints must not be nested");
+        tys.undo = Some(Vec::new());
+        Checkpoint {
+            num_types: tys.nodes.len(),
         }
     }
+    /// Keeps the changes to the types since `checkpoint`.
+    pub fn commit(&self, _checkpoint: Checkpoint) {
+        self.0.borrow_mut().undo = None;
+    }
+    /// Undoes the changes to the types since `checkpoint`, and frees the types made since, none of which may be used afterwards.
+    pub fn rollback(&self, checkpoint: Checkpoint) {
+        let mut tys = self.0.borrow_mut();
+        let undo = tys.undo.take().expect("a checkpoint must be recording");
+        for (index, node) in undo.into_iter().rev() {
+            tys.nodes[index] = node;
+        }
+        tys.nodes.truncate(checkpoint.num_types);
+    }
 }
 
+/// The state of the types at a call to `TyTable::checkpoint`.
+pub struct Checkpoint {
+    num_types: usize,
+}
+
+impl Ty {
+    fn nodes(&self) -> Rc<RefCell<TyNodes>> {
+        self.nodes.upgrade().expect("a type must not outlive its table")
+    }
+    /// The table of `self`, where the types made from it go.
+    fn table(&self) -> TyTable {
+        TyTable(self.nodes())
+    }
+    /// The representative of the class of `self`. Every type on the way is made to point to it directly.
+    fn find(&self) -> usize {
+        let nodes = self.nodes();
+        let mut tys = nodes.borrow_mut();
+        let mut root = self.index;
+        while tys.nodes[root].parent != root {
+            root = tys.nodes[root].parent;
+        }
+        let mut index = self.index;
+        while tys.nodes[index].parent != root {
+            index = std::mem::replace(&mut tys.node_mut(index).parent, root);
+        }
+        root
+    }
+    /// The structure of `self`.
+    fn inner(&self) -> Rc<TyInner> {
+        let root = self.find();
+        self.nodes().borrow().nodes[root].inner.clone()
+    }
+    /// Whether `self` and `other` have been made equal.
+    fn same(&self, other: &Ty) -> bool {
+        self.find() == other.find()
+    }
+    /// Makes `self`, which must be undetermined, equal to `other`, putting the class of lower rank under the other.
+    fn bind(&self, other: &Ty) {
+        debug_assert!(self.nodes.ptr_eq(&other.nodes), "types of different tables must not be unified");
+        let (var, ty) = (self.find(), other.find());
+        if var == ty {
+            return;
+        }
+        let nodes = self.nodes();
+        let mut tys = nodes.borrow_mut();
+        let (root, child) = if tys.nodes[var].rank > tys.nodes[ty].rank {
+            tys.node_mut(var).inner = tys.nodes[ty].inner.clone();
+            (var, ty)
+        } else {
+            (ty, var)
+        };
+        tys.node_mut(child).parent = root;
+        if tys.nodes[var].rank == tys.nodes[ty].rank {
+            tys.node_mut(root).rank += 1;
+        }
+    }
+    /// Gives `self`, which must be undetermined, the structure `inner`.
+    fn set(&self, inner: TyInner) {
+        let root = self.find();
+        self.nodes().borrow_mut().node_mut(root).inner = Rc::new(inner);
+    }
+}
+
+#[derive(Clone)]
 pub enum TyInner {
     Int,
+    Float,
+    Bool,
+    Str,
     Ref(Ty),
     Func { args: Vec<Ty>, ret: Ty },
+    Tuple(Vec<Ty>),
+    /// The type declared with `type` as the `id`-th one, which is equal only to itself.
+    Named {
+        id: usize,
+        name: Rc<str>,
+    },
     Undetermined,
-    SameAs(Ty),
 }
 
 impl Ty {
-    fn get_args_ret(&self) -> (Vec<Ty>, Ty) {
-        match *self.inner.borrow() {
-            TyInner::SameAs(ref ty) => ty.get_args_ret(),
-            TyInner::Func { ref args, ref ret } => (args.clone(), ret.clone()),
-            _ => panic!("not a function"),
+    /// The type that `self` returns after every call, which is `self` itself if it is not a function.
+    fn atom(&self) -> Ty {
+        self.levels().1
+    }
+    /// The number of levels of calls to `self`, and the type that the last call returns.
+    fn levels(&self) -> (usize, Ty) {
+        let mut levels = 0;
+        let mut ty = self.clone();
+        while let Some(ret) = ty.ret() {
+            levels += 1;
+            ty = ret;
         }
+        (levels, ty)
     }
-    fn unify(&self, other: &Ty) -> Option<VecDeque<Vec<Ty>>> {
-        let self_binding = self.inner.borrow();
-        let other_binding = other.inner.borrow();
-        match (&*self_binding, &*other_binding) {
-            (_, TyInner::SameAs(other_equiv)) => {
-                drop(self_binding);
-                self.unify(other_equiv)
-            }
-            (TyInner::SameAs(self_equiv), _) => {
-                drop(other_binding);
-                self_equiv.unify(other)
+    fn is_undetermined(&self) -> bool {
+        matches!(*self.inner(), TyInner::Undetermined)
+    }
+    /// The parameter and return types of `self`, which is made a function of `arity` parameters if it is undetermined,
+    /// as the type of a global variable called in its own definition.
+    fn as_func(&self, arity: usize) -> Option<(Vec<Ty>, Ty)> {
+        if let Some(args_ret) = self.get_args_ret() {
+            return Some(args_ret);
+        }
+        let ty = self.atom();
+        if !ty.is_undetermined() {
+            return None;
+        }
+        let tys = ty.table();
+        let args: Vec<_> = (0..arity).map(|_| tys.new_ty(TyInner::Undetermined)).collect();
+        let ret = tys.new_ty(TyInner::Undetermined);
+        ty.set(TyInner::Func {
+            args: args.clone(),
+            ret: ret.clone(),
+        });
+        Some((args, ret))
+    }
+    fn ret(&self) -> Option<Ty> {
+        match *self.inner() {
+            TyInner::Func { ref ret, .. } => Some(ret.clone()),
+            _ => None,
+        }
+    }
+    fn get_args_ret(&self) -> Option<(Vec<Ty>, Ty)> {
+        match *self.inner() {
+            TyInner::Func { ref args, ref ret } => Some((args.clone(), ret.clone())),
+            _ => None,
+        }
+    }
+    /// Unifies `self` with `other` as an argument lifted over `depth` levels of parameters if `self` is a function of that many levels,
+    /// and as `unify` does otherwise.
+    fn unify_lifted(&self, other: &Ty, depth: usize) -> Result<VecDeque<Vec<Ty>>, UnifyError> {
+        let mut params = Vec::new();
+        let mut ret = self.clone();
+        while params.len() < depth {
+            let Some((args, next)) = ret.get_args_ret() else {
+                return self.unify(other);
+            };
+            params.push(args);
+            ret = next;
+        }
+        let mut extra_calls = ret.unify(other)?;
+        extra_calls.extend(params.into_iter().rev());
+        Ok(extra_calls)
+    }
+    /// Fails if `self`, an undetermined type, occurs in `ty`, so that binding it to `ty` would make an infinite type.
+    fn occurs_check(&self, ty: &Ty) -> Result<(), UnifyError> {
+        if self.occurs_in(ty) {
+            Err(UnifyError::Infinite {
+                var: self.clone(),
+                ty: ty.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+    fn occurs_in(&self, ty: &Ty) -> bool {
+        if self.same(ty) {
+            return true;
+        }
+        match *ty.inner() {
+            TyInner::Ref(ref ty) => self.occurs_in(ty),
+            TyInner::Func { ref args, ref ret } => {
+                args.iter().any(|arg| self.occurs_in(arg)) || self.occurs_in(ret)
             }
+            TyInner::Tuple(ref items) => items.iter().any(|item| self.occurs_in(item)),
+            TyInner::Int
+            | TyInner::Float
+            | TyInner::Bool
+            | TyInner::Str
+            | TyInner::Named { .. }
+            | TyInner::Undetermined => false,
+        }
+    }
+    fn unify(&self, other: &Ty) -> Result<VecDeque<Vec<Ty>>, UnifyError> {
+        if self.same(other) {
+            return Ok(VecDeque::new());
+        }
+        match (&*self.inner(), &*other.inner()) {
             (_, TyInner::Undetermined) => {
-                drop(other_binding);
-                *other.inner.borrow_mut() = TyInner::SameAs(self.clone());
-                Some(VecDeque::new())
+                other.occurs_check(self)?;
+                other.bind(self);
+                Ok(VecDeque::new())
             }
             (TyInner::Undetermined, _) => {
-                drop(self_binding);
-                *self.inner.borrow_mut() = TyInner::SameAs(other.clone());
-                Some(VecDeque::new())
-            }
-            (TyInner::Int, TyInner::Int) => Some(VecDeque::new()),
-            (TyInner::Ref(self_0), TyInner::Ref(other_0)) => self_0.unify(other_0),
-            (_, TyInner::Func { args: _, ret }) => {
-                drop(self_binding);
-                let mut tmp = self.unify(ret)?;
-                tmp.pop_front();
-                Some(tmp)
+                self.occurs_check(other)?;
+                self.bind(other);
+                Ok(VecDeque::new())
+            }
+            (TyInner::Int, TyInner::Int)
+            | (TyInner::Float, TyInner::Float)
+            | (TyInner::Bool, TyInner::Bool)
+            | (TyInner::Str, TyInner::Str) => Ok(VecDeque::new()),
+            (TyInner::Named { id: self_id, .. }, TyInner::Named { id: other_id, .. })
+                if self_id == other_id =>
+            {
+                Ok(VecDeque::new())
+            }
+            // References and tuples are neither lifted nor converted over their contents.
+            (TyInner::Ref(self_0), TyInner::Ref(other_0)) => {
+                self_0.equate(other_0)?;
+                Ok(VecDeque::new())
+            }
+            (TyInner::Tuple(self_items), TyInner::Tuple(other_items)) => {
+                if self_items.len() != other_items.len() {
+                    return Err(UnifyError::Mismatch);
+                }
+                for (self_item, other_item) in self_items.iter().zip(other_items) {
+                    self_item.equate(other_item)?;
+                }
+                Ok(VecDeque::new())
+            }
+            // A function of more levels than `other` is lifted over the outer ones that `other` lacks, as below,
+            // and otherwise related to `other` by S-Arrow or S-Const, which `check` makes conversions for.
+            (TyInner::Func { .. }, TyInner::Func { .. }) => {
+                let (levels, _) = self.levels();
+                let (other_levels, other_atom) = other.levels();
+                if other_atom.is_undetermined() || levels <= other_levels {
+                    subsume(self, other)?;
+                    return Ok(VecDeque::new());
+                }
+                let mut extra_calls = VecDeque::new();
+                let mut ret = self.clone();
+                for _ in other_levels..levels {
+                    let (args, next) = ret.get_args_ret().expect("a lifted level must be a call");
+                    extra_calls.push_front(args);
+                    ret = next;
+                }
+                subsume(&ret, other)?;
+                Ok(extra_calls)
             }
             (TyInner::Func { args, ret }, _) => {
-                drop(other_binding);
                 let mut tmp = ret.unify(other)?;
                 tmp.push_back(args.clone());
-                Some(tmp)
+                Ok(tmp)
             }
-            _ => None,
+            // `Int <: Float`, and S-Const for a value passed as a function.
+            _ => {
+                subsume(self, other)?;
+                Ok(VecDeque::new())
+            }
+        }
+    }
+    /// Unifies `self` with `other`, which it must then equal, as the contents of references.
+    fn equate(&self, other: &Ty) -> Result<(), UnifyError> {
+        if self.unify(other)?.is_empty() && self.equals(other) {
+            Ok(())
+        } else {
+            Err(UnifyError::Mismatch)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TyTable;
+    use crate::{context::Context, parser};
+
+    /// Checks and evaluates the expression `input`, giving it with its conversions, its type and its value.
+    fn eval(input: &str) -> String {
+        let (mut statements, errors) = parser::parse(input, 0);
+        assert!(errors.is_empty(), "`{input}` must parse");
+        let tys = TyTable::new();
+        let mut expr = Context::new(&tys)
+            .translate_stmt(statements.remove(0))
+            .ok()
+            .flatten()
+            .expect("the input must be an expression");
+        let Ok(ty) = expr.check(&tys, &[]) else {
+            panic!("`{input}` must type-check");
+        };
+        let Ok(value) = expr.eval(&[], &None) else {
+            panic!("`{input}` must evaluate");
+        };
+        format!("{expr}: {ty} = {value}")
+    }
+
+    #[test]
+    fn ints_are_converted_to_floats() {
+        assert_eq!(eval("1 + 2.5"), "Add(ToFloat(1), 2.5): Float = 3.5");
+        assert_eq!(eval("7 / 2"), "Div(7, 2): Int = 3");
+        assert_eq!(eval("7.0 / 2"), "Div(7.0, ToFloat(2)): Float = 3.5");
+        assert_eq!(eval("1 < 1.5"), "Lt(ToFloat(1), 1.5): Bool = true");
+        assert_eq!(eval("(-2.5e1) % 7"), "Rem(-25.0, ToFloat(7)): Float = -4.0");
+        // The conversion lifts over a function returning `Int`.
+        assert_eq!(
+            eval("([Int] * 0.5)(3)"),
+            "Mul(ToFloat(Id[Int]), 0.5)(3): Float = 1.5"
+        );
+        assert_eq!(
+            eval("((x: Float) => x)(1)"),
+            "Id[(Float)Float](Lambda[Float](Deref[Float](&l0.0)))(ToFloat(1)): Float = 1.0"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+impl Expr {
+    /// Finds the first read of a global variable that may happen before anything is assigned to it,
+    /// and marks in `assigned` the variables that `self` assigns.
+    /// `assigned` must tell which variables were assigned by the earlier statements.
+    ///
+    /// The body of a lambda runs whenever the lambda is called, so a read in it only needs the
+    /// variable to be assigned by the end of the statement, as in `f = (n) => f(n)`.
+    /// Local variables need no check, because their names are bound only after they are assigned.
+    pub fn unassigned_read(&self, assigned: &mut [bool]) -> Option<(usize, Span)> {
+        let mut reads = Vec::new();
+        let mut deferred = Vec::new();
+        self.find_unassigned(assigned, &mut reads, &mut deferred);
+        reads.extend(deferred.into_iter().filter(|&(idx, _)| !assigned[idx]));
+        reads.into_iter().min_by_key(|&(_, span)| span.start)
+    }
+    /// Visits `self` in the order of evaluation. Unassigned reads go to `reads`,
+    /// and those in a lambda body, which are decided at the end of the statement, go to `deferred`.
+    fn find_unassigned(
+        &self,
+        assigned: &mut [bool],
+        reads: &mut Vec<(usize, Span)>,
+        deferred: &mut Vec<(usize, Span)>,
+    ) {
+        match self.kind {
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Str(_)
+            | ExprKind::Var(_)
+            | ExprKind::Local { .. } => {}
+            ExprKind::Lambda { ref body, .. } => {
+                // Assignments in the body happen only if the lambda is called.
+                let mut assigned_in_body = assigned.to_vec();
+                let mut reads_in_body = Vec::new();
+                body.find_unassigned(&mut assigned_in_body, &mut reads_in_body, deferred);
+                deferred.extend(reads_in_body);
+            }
+            ExprKind::Tuple(ref body) | ExprKind::Block { ref body, .. } => {
+                for expr in body {
+                    expr.find_unassigned(assigned, reads, deferred);
+                }
+            }
+            ExprKind::Func {
+                ref func,
+                ref calls,
+            } => {
+                for (i, call) in calls.iter().enumerate() {
+                    if i == 0 && matches!(func, Func::If(_) | Func::And | Func::Or) {
+                        find_unassigned_lazily(func, &call.args, assigned, reads, deferred);
+                        continue;
+                    }
+                    for arg in &call.args {
+                        arg.find_unassigned(assigned, reads, deferred);
+                    }
+                    if i > 0 {
+                        continue;
+                    }
+                    match (func, &call.args[..]) {
+                        (
+                            Func::Deref(_),
+                            [Expr {
+                                kind: ExprKind::Var(idx),
+                                span,
+                            }],
+                        ) if !assigned[*idx] => reads.push((*idx, *span)),
+                        (
+                            Func::Assign(_),
+                            [Expr {
+                                kind: ExprKind::Var(idx),
+                                ..
+                            }, _],
+                        ) => assigned[*idx] = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Visits the operands of `if`, `&&` or `||`, of which only the first is sure to be evaluated.
+/// Afterwards, a variable is assigned only if it is on every path.
+fn find_unassigned_lazily(
+    func: &Func,
+    args: &[Expr],
+    assigned: &mut [bool],
+    reads: &mut Vec<(usize, Span)>,
+    deferred: &mut Vec<(usize, Span)>,
+) {
+    let Some((cond, branches)) = args.split_first() else {
+        return;
+    };
+    cond.find_unassigned(assigned, reads, deferred);
+    // `&&` and `||` may skip their second operand altogether.
+    let mut on_every_path = match func {
+        Func::If(_) => None,
+        _ => Some(assigned.to_vec()),
+    };
+    for branch in branches {
+        let mut assigned_in_branch = assigned.to_vec();
+        branch.find_unassigned(&mut assigned_in_branch, reads, deferred);
+        on_every_path = Some(match on_every_path {
+            Some(prev) => prev
+                .iter()
+                .zip(&assigned_in_branch)
+                .map(|(&x, &y)| x && y)
+                .collect(),
+            None => assigned_in_branch,
+        });
+    }
+    if let Some(on_every_path) = on_every_path {
+        assigned.copy_from_slice(&on_every_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{context::Context, ir::TyTable, parser};
+
+    /// The name of the first variable that the last statement of `source` may read before it is assigned,
+    /// when the statements before it have run.
+    fn unassigned(source: &str) -> Option<String> {
+        let (statements, errors) = parser::parse(source, 0);
+        assert!(errors.is_empty(), "`{source}` has a syntax error");
+        let mut context = Context::new(&TyTable::new());
+        let mut assigned = Vec::new();
+        let mut read = None;
+        for statement in statements {
+            let Ok(Some(expr)) = context.translate_stmt(statement) else {
+                panic!("`{source}` must translate to expressions")
+            };
+            assigned.resize(context.num_variables(), false);
+            read = expr.unassigned_read(&mut assigned);
+        }
+        let (idx, _) = read?;
+        let variables = context.variables();
+        let &(name, _) = variables.iter().find(|&&(_, other)| other == idx)?;
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn assignments_in_order() {
+        assert_eq!(unassigned("x = 1\ny = x"), None);
+        assert_eq!(unassigned("y = x\nx = 1"), None);
+        assert_eq!(unassigned("x = 1\ny = z"), Some("z".to_string()));
+        assert_eq!(unassigned("x = { y = 1; y } + y"), None);
+        assert_eq!(unassigned("x = y + { y = 1; y }"), Some("y".to_string()));
+    }
+
+    #[test]
+    fn if_assigns_what_both_branches_assign() {
+        assert_eq!(
+            unassigned("if true then { x = 1; x } else { x = 2; x }\ny = x"),
+            None
+        );
+        assert_eq!(
+            unassigned("if true then { x = 1; x } else 2\ny = x"),
+            Some("x".to_string())
+        );
+        assert_eq!(unassigned("if { x = true; x } then x else x"), None);
+        assert_eq!(
+            unassigned("if true then { x = 1; x } else x"),
+            Some("x".to_string())
+        );
+    }
+
+    #[test]
+    fn and_or_may_skip_their_second_operand() {
+        assert_eq!(unassigned("{ x = true; x } && x"), None);
+        assert_eq!(unassigned("{ x = true; x } || x"), None);
+        assert_eq!(
+            unassigned("true && { x = true; x }\ny = x"),
+            Some("x".to_string())
+        );
+        assert_eq!(
+            unassigned("false || { x = true; x }\ny = x"),
+            Some("x".to_string())
+        );
+    }
+
+    #[test]
+    fn lambda_bodies_are_deferred() {
+        assert_eq!(unassigned("f = (n) => f(n)"), None);
+        assert_eq!(unassigned("g = ((n) => h, { h = 1; h })"), None);
+        assert_eq!(unassigned("g = (n) => h"), Some("h".to_string()));
+        // An assignment in the body happens only when the lambda is called.
+        assert_eq!(unassigned("f = (n) => x = n\ny = x"), Some("x".to_string()));
+    }
+}
@@ -0,0 +1,262 @@
+use super::{
+    subtype::{Derivation, Rule},
+    *,
+};
+use std::fmt::Write;
+
+/// A typing derivation by the rules of `note/lang.tex`, recorded by `Expr::derive`.
+/// The types are kept as they are, so that they are printed as far as the type check determined them.
+pub struct Proof {
+    /// `None` for a premise taken as given, as `x : T \in \Gamma`.
+    rule: Option<&'static str>,
+    conclusion: Judgment,
+    premises: Vec<Proof>,
+}
+
+enum Judgment {
+    /// `term : ty \in \Gamma`.
+    InEnv { term: String, ty: Ty },
+    /// `\Gamma \vdash term : ty`.
+    Typing { term: String, ty: Ty },
+    /// `sub \sub sup`, with the macro of the note for the subtyping relation.
+    Subtype { sub: Ty, sup: Ty },
+}
+
+/// The most premises that `bussproofs` can put above one line.
+const MAX_PREMISES: usize = 5;
+
+impl Proof {
+    /// T-Var for `term`, a variable or a constant such as a literal or a built-in function.
+    pub(super) fn var(term: String, ty: Ty) -> Proof {
+        Proof {
+            rule: Some("T-Var"),
+            conclusion: Judgment::Typing {
+                term: term.clone(),
+                ty: ty.clone(),
+            },
+            premises: vec![Proof {
+                rule: None,
+                conclusion: Judgment::InEnv { term, ty },
+                premises: Vec::new(),
+            }],
+        }
+    }
+    /// T-App for `func` applied to `args`, whose types must already be lifted alike.
+    pub(super) fn app(func: Proof, args: Vec<Proof>, ty: Ty) -> Proof {
+        let term = format!(
+            "{}({})",
+            func.term(),
+            args.iter().map(Proof::term).collect::<Vec<_>>().join(", ")
+        );
+        Proof::rule("T-App", term, ty, iter::once(func).chain(args).collect())
+    }
+    /// `rule` of the note concluding that `term` has type `ty`.
+    fn rule(rule: &'static str, term: String, ty: Ty, premises: Vec<Proof>) -> Proof {
+        Proof {
+            rule: Some(rule),
+            conclusion: Judgment::Typing { term, ty },
+            premises,
+        }
+    }
+    /// T-Sub with S-Const, which lifts `self` to a function that takes `params` and ignores them.
+    pub(super) fn lift(self, params: Vec<Ty>) -> Proof {
+        let ty = self.ty();
+        let sup = ty.table().new_ty(TyInner::Func {
+            args: params,
+            ret: ty.clone(),
+        });
+        let subtyping = Proof::subtyping("S-Const", ty, sup.clone(), Vec::new());
+        self.subsume(sup, subtyping)
+    }
+    /// T-Sub with the subtyping derived by `derivation`, translated into the declarative rules.
+    pub(super) fn coerce(self, derivation: &Derivation) -> Proof {
+        self.subsume(derivation.sup.clone(), Proof::declarative(derivation))
+    }
+    fn subsume(self, sup: Ty, subtyping: Proof) -> Proof {
+        let term = self.term().to_string();
+        Proof::rule("T-Sub", term, sup, vec![self, subtyping])
+    }
+    fn subtyping(rule: &'static str, sub: Ty, sup: Ty, premises: Vec<Proof>) -> Proof {
+        Proof {
+            rule: Some(rule),
+            conclusion: Judgment::Subtype { sub, sup },
+            premises,
+        }
+    }
+    /// Translates an algorithmic derivation: SA-Atom is S-Refl, SA-Int is S-Int, SA-Arrow is S-Arrow,
+    /// and SA-Const is S-Const, after S-Trans unless its premise is by S-Refl.
+    fn declarative(derivation: &Derivation) -> Proof {
+        let (sub, sup) = (derivation.sub.clone(), derivation.sup.clone());
+        let premises: Vec<_> = derivation
+            .premises
+            .iter()
+            .map(|premise| Proof::declarative(premise))
+            .collect();
+        match derivation.rule {
+            Rule::Atom => Proof::subtyping("S-Refl", sub, sup, Vec::new()),
+            Rule::Int => Proof::subtyping("S-Int", sub, sup, Vec::new()),
+            Rule::Arrow => Proof::subtyping("S-Arrow", sub, sup, premises),
+            Rule::Const => {
+                let premise = &derivation.premises[0];
+                let constant = Proof::subtyping("S-Const", premise.sup.clone(), sup.clone(), Vec::new());
+                if premise.rule == Rule::Atom {
+                    return Proof::subtyping("S-Const", sub, sup, Vec::new());
+                }
+                let premises = premises.into_iter().chain([constant]).collect();
+                Proof::subtyping("S-Trans", sub, sup, premises)
+            }
+        }
+    }
+    pub(super) fn term(&self) -> &str {
+        match self.conclusion {
+            Judgment::InEnv { ref term, .. } | Judgment::Typing { ref term, .. } => term,
+            Judgment::Subtype { .. } => unreachable!("a subtyping has no term"),
+        }
+    }
+    pub(super) fn ty(&self) -> Ty {
+        match self.conclusion {
+            Judgment::InEnv { ref ty, .. } | Judgment::Typing { ref ty, .. } => ty.clone(),
+            Judgment::Subtype { .. } => unreachable!("a subtyping has no type"),
+        }
+    }
+    /// Prints `self` as a `prooftree` environment of `bussproofs`.
+    /// A step with more premises than `bussproofs` allows shows `\dotsc` in place of the middle ones,
+    /// each of which follows as a `prooftree` of its own.
+    pub fn to_tex(&self) -> String {
+        let mut out = String::new();
+        let mut pending = vec![self];
+        while let Some(proof) = pending.pop() {
+            let mut elided = Vec::new();
+            out.push_str("\\begin{prooftree}\n");
+            proof.write_tex(&mut out, &mut elided);
+            out.push_str("\\end{prooftree}\n");
+            pending.extend(elided.into_iter().rev());
+        }
+        out
+    }
+    fn write_tex<'a>(&'a self, out: &mut String, elided: &mut Vec<&'a Proof>) {
+        let conclusion = self.conclusion.tex();
+        let Some(rule) = self.rule else {
+            writeln!(out, "\\AxiomC{{${conclusion}$}}").unwrap();
+            return;
+        };
+        let premises = if self.premises.len() > MAX_PREMISES {
+            let (shown, rest) = self.premises.split_at(MAX_PREMISES - 2);
+            let (middle, last) = rest.split_at(rest.len() - 1);
+            elided.extend(middle);
+            for premise in shown {
+                premise.write_tex(out, elided);
+            }
+            out.push_str("\\AxiomC{$\\dotsc$}\n");
+            last[0].write_tex(out, elided);
+            MAX_PREMISES
+        } else if self.premises.is_empty() {
+            out.push_str("\\AxiomC{}\n");
+            1
+        } else {
+            for premise in &self.premises {
+                premise.write_tex(out, elided);
+            }
+            self.premises.len()
+        };
+        let inference = ["Unary", "Binary", "Trinary", "Quaternary", "Quinary"][premises - 1];
+        writeln!(out, "\\LeftLabel{{\\textsc{{{rule}}}: }}").unwrap();
+        writeln!(out, "\\{inference}InfC{{${conclusion}$}}").unwrap();
+    }
+}
+
+impl Judgment {
+    fn tex(&self) -> String {
+        match self {
+            Judgment::InEnv { term, ty } => format!("{} : {} \\in \\Gamma", tex_term(term), ty.tex()),
+            Judgment::Typing { term, ty } => {
+                format!("\\Gamma \\vdash {} : {}", tex_term(term), ty.tex())
+            }
+            Judgment::Subtype { sub, sup } => format!("{} \\sub {}", sub.tex(), sup.tex()),
+        }
+    }
+}
+
+/// `term` in typewriter type, with the characters special to TeX escaped.
+fn tex_term(term: &str) -> String {
+    let mut escaped = String::new();
+    for ch in term.chars() {
+        match ch {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '^' => escaped.push_str("\\^{}"),
+            '~' => escaped.push_str("\\~{}"),
+            _ => escaped.push(ch),
+        }
+    }
+    format!("\\texttt{{{escaped}}}")
+}
+
+impl Ty {
+    /// `self` in the notation of `note/lang.tex`, as `(\mathrm{Int} \, \mathrm{Int}) \, \mathrm{Int}`.
+    fn tex(&self) -> String {
+        self.tex_visiting(&mut Vec::new())
+    }
+    /// Writes `self` as `tex` does, with `\ldots` where a type recurs as `fmt_visiting` writes `...`.
+    fn tex_visiting(&self, visiting: &mut Vec<usize>) -> String {
+        let written = self.visit(visiting, |visiting| {
+            let mut tex = |ty: &Ty| ty.tex_visiting(visiting);
+            match *self.inner() {
+                TyInner::Int => "\\mathrm{Int}".to_string(),
+                TyInner::Float => "\\mathrm{Float}".to_string(),
+                TyInner::Bool => "\\mathrm{Bool}".to_string(),
+                TyInner::Str => "\\mathrm{Str}".to_string(),
+                TyInner::Named { ref name, .. } => format!("\\mathrm{{{name}}}"),
+                TyInner::Ref(ref ty) => format!("\\mathrm{{Ref}}[{}]", tex(ty)),
+                TyInner::Func { ref args, ref ret } => {
+                    let args: Vec<_> = args.iter().map(&mut tex).collect();
+                    format!("({}) \\, {}", args.join(" \\, "), tex(ret))
+                }
+                TyInner::Tuple(ref items) => {
+                    let items: Vec<_> = items.iter().map(&mut tex).collect();
+                    format!("\\langle {} \\rangle", items.join(", "))
+                }
+                TyInner::Undetermined => "?".to_string(),
+            }
+        });
+        written.unwrap_or_else(|| "\\ldots".to_string())
+    }
+}
+
+impl Func {
+    /// The name of `self` as a term, without the type that `Display` shows.
+    pub(super) fn term(&self) -> String {
+        match self {
+            Func::Id(_) => "Id".to_string(),
+            Func::Deref(_) => "Deref".to_string(),
+            Func::Assign(_) => "Assign".to_string(),
+            Func::If(_) => "If".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cyclic_types() {
+        let tys = TyTable::new();
+        let ty = tys.new_ty(TyInner::Undetermined);
+        let func = tys.new_ty(TyInner::Func {
+            args: vec![tys.new_ty(TyInner::Int)],
+            ret: ty.clone(),
+        });
+        ty.bind(&func);
+        assert_eq!(func.tex(), "(\\mathrm{Int}) \\, \\ldots");
+        let subtyping = Proof::subtyping("S-Refl", func.clone(), ty, Vec::new());
+        assert_eq!(
+            subtyping.conclusion.tex(),
+            "(\\mathrm{Int}) \\, \\ldots \\sub (\\mathrm{Int}) \\, \\ldots"
+        );
+    }
+}
@@ -1,12 +1,48 @@
 use super::*;
+use crate::diagnostic::Diagnostic;
 use std::fmt::{self, Display, Formatter};
 
 impl Display for Expr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Expr::Int(value) => write!(f, "{value}"),
-            Expr::Var(idx) => write!(f, "&v{idx}"),
-            Expr::Func { func, calls } => {
+        match &self.kind {
+            ExprKind::Int(value) => write!(f, "{value}"),
+            ExprKind::Float(value) => write!(f, "{value:?}"),
+            ExprKind::Bool(value) => write!(f, "{value}"),
+            ExprKind::Str(value) => write!(f, "{value:?}"),
+            ExprKind::Var(idx) => write!(f, "&v{idx}"),
+            ExprKind::Local { depth, index, .. } => write!(f, "&l{depth}.{index}"),
+            ExprKind::Lambda { params, body } => write!(
+                f,
+                "Lambda[{}]({body})",
+                params
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Tuple(items) => write!(
+                f,
+                "Tuple({})",
+                items
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Block { locals, body } => write!(
+                f,
+                "Block[{}]({})",
+                locals
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                body.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            ExprKind::Func { func, calls } => {
                 write!(f, "{func}")?;
                 for call in calls {
                     write!(
@@ -34,6 +70,37 @@ impl Display for Func {
             Func::Mul => write!(f, "Mul"),
             Func::Div => write!(f, "Div"),
             Func::Rem => write!(f, "Rem"),
+            Func::Neg => write!(f, "Neg"),
+            Func::ToFloat => write!(f, "ToFloat"),
+            Func::ToConst(params) => write!(
+                f,
+                "ToConst[{}]",
+                params
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Func::Convert(derivation) => {
+                write!(f, "Convert[{} <: {}]", derivation.sub, derivation.sup)
+            }
+            Func::Len => write!(f, "Len"),
+            Func::ToStr => write!(f, "ToStr"),
+            Func::Eq => write!(f, "Eq"),
+            Func::Ne => write!(f, "Ne"),
+            Func::Lt => write!(f, "Lt"),
+            Func::Le => write!(f, "Le"),
+            Func::Gt => write!(f, "Gt"),
+            Func::Ge => write!(f, "Ge"),
+            Func::And => write!(f, "And"),
+            Func::Or => write!(f, "Or"),
+            Func::Not => write!(f, "Not"),
+            Func::If(ty) => write!(f, "If[{ty}]"),
+            Func::Proj(index) => write!(f, "Proj[{index}]"),
+            Func::Unpack(len) => write!(f, "Unpack[{len}]"),
+            Func::Ctor(ctor) => write!(f, "{}", ctor.name),
+            Func::Is(ctor) => write!(f, "Is[{}]", ctor.name),
+            Func::Field(ctor, index) => write!(f, "Field[{}.{index}]", ctor.name),
             Func::Assign(ty) => write!(f, "Assign[{ty}]"),
             Func::Deref(ty) => write!(f, "Deref[{ty}]"),
         }
@@ -42,37 +109,79 @@ impl Display for Func {
 
 impl Display for Ty {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        /*{
-            use std::hash::{DefaultHasher, Hash, Hasher};
-            let mut hasher = DefaultHasher::new();
-            Rc::as_ptr(&self.inner).hash(&mut hasher);
-            write!(f, "{} ", hasher.finish() % 1000)?;
-        }*/
-        match *self.inner.borrow() {
-            TyInner::Int => {
-                write!(f, "Int")
+        self.fmt_visiting(f, &mut Vec::new())
+    }
+}
+
+impl Ty {
+    /// Calls `write` with `self` added to `visiting`, the types being written that contain it.
+    /// Returns `None` instead if `self` is already one of them, so that a type containing itself,
+    /// which the occurs check in `unify` should prevent, is not written forever.
+    pub(super) fn visit<T>(
+        &self,
+        visiting: &mut Vec<usize>,
+        write: impl FnOnce(&mut Vec<usize>) -> T,
+    ) -> Option<T> {
+        let root = self.find();
+        if visiting.contains(&root) {
+            return None;
+        }
+        visiting.push(root);
+        let result = write(visiting);
+        visiting.pop();
+        Some(result)
+    }
+    /// Writes `self` as `Display` does, with `...` where a type recurs.
+    fn fmt_visiting(
+        &self,
+        f: &mut Formatter<'_>,
+        visiting: &mut Vec<usize>,
+    ) -> fmt::Result {
+        let written = self.visit(visiting, |visiting| match *self.inner() {
+            TyInner::Int => write!(f, "Int"),
+            TyInner::Float => write!(f, "Float"),
+            TyInner::Bool => write!(f, "Bool"),
+            TyInner::Str => write!(f, "Str"),
+            TyInner::Ref(ref ty) => {
+                write!(f, "Ref[")?;
+                ty.fmt_visiting(f, visiting)?;
+                write!(f, "]")
             }
-            TyInner::Ref(ref ty) => write!(f, "Ref[{ty}]"),
             TyInner::Undetermined => write!(f, "?"),
             TyInner::Func { ref args, ref ret } => {
-                write!(
-                    f,
-                    "({}){ret}",
-                    args.iter()
-                        .map(|arg| format!("{arg}"))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
+                fmt_list(f, args, visiting)?;
+                ret.fmt_visiting(f, visiting)
             }
-            TyInner::SameAs(ref ty) => write!(f, "{ty}"),
+            TyInner::Tuple(ref items) => fmt_list(f, items, visiting),
+            TyInner::Named { ref name, .. } => write!(f, "{name}"),
+        });
+        written.unwrap_or_else(|| write!(f, "..."))
+    }
+}
+
+/// Writes `tys` separated by commas in parentheses.
+fn fmt_list(
+    f: &mut Formatter<'_>,
+    tys: &[Ty],
+    visiting: &mut Vec<usize>,
+) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, ty) in tys.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
         }
+        ty.fmt_visiting(f, visiting)?;
     }
+    write!(f, ")")
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Value::Int(value) => write!(f, "{value}"),
+            Value::Float(value) => write!(f, "{value:?}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Str(value) => write!(f, "{value:?}"),
             Value::Var(var) => match *var.borrow() {
                 Some(ref value) => write!(f, "-> {}", value),
                 None => write!(f, "uninitialized"),
@@ -83,6 +192,46 @@ impl Display for Value {
             Value::Mul => write!(f, "Mul"),
             Value::Div => write!(f, "Div"),
             Value::Rem => write!(f, "Rem"),
+            Value::Neg => write!(f, "Neg"),
+            Value::ToFloat => write!(f, "ToFloat"),
+            Value::ToConst => write!(f, "ToConst"),
+            Value::Convert(_) => write!(f, "Convert"),
+            Value::Len => write!(f, "Len"),
+            Value::ToStr => write!(f, "ToStr"),
+            Value::Eq => write!(f, "Eq"),
+            Value::Ne => write!(f, "Ne"),
+            Value::Lt => write!(f, "Lt"),
+            Value::Le => write!(f, "Le"),
+            Value::Gt => write!(f, "Gt"),
+            Value::Ge => write!(f, "Ge"),
+            Value::And => write!(f, "And"),
+            Value::Or => write!(f, "Or"),
+            Value::Not => write!(f, "Not"),
+            Value::If => write!(f, "If"),
+            Value::Proj(index) => write!(f, "Proj[{index}]"),
+            Value::Tuple(items) => write!(
+                f,
+                "({})",
+                items
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Ctor(ctor) => write!(f, "{}", ctor.name),
+            Value::Is(ctor) => write!(f, "Is[{}]", ctor.name),
+            Value::Field(ctor, index) => write!(f, "Field[{}.{index}]", ctor.name),
+            Value::Data(ctor, fields) if fields.is_empty() => write!(f, "{}", ctor.name),
+            Value::Data(ctor, fields) => write!(
+                f,
+                "{}({})",
+                ctor.name,
+                fields
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             Value::Assign => write!(f, "Assign"),
             Value::Deref => write!(f, "Deref"),
             Value::Curry => write!(f, "Curry"),
@@ -94,7 +243,133 @@ impl Display for Value {
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
+            Value::Lifted {
+                func: lifted,
+                operands,
+                args,
+                ..
+            } => {
+                write!(f, "{lifted}")?;
+                for values in iter::once(operands).chain(args) {
+                    write!(
+                        f,
+                        "({})",
+                        values
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )?;
+                }
+                Ok(())
+            }
             Value::Const(value) => write!(f, "const {value}"),
+            Value::Converted(func, _) => write!(f, "Convert({func})"),
+            Value::Closure(closure) => write!(
+                f,
+                "Lambda[{}]({})",
+                closure
+                    .params
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                closure.body
+            ),
         }
     }
 }
+
+impl TypeError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            TypeError::Mismatch {
+                expected,
+                found,
+                position,
+                span,
+            } => Diagnostic::error(
+                format!("mismatched types in argument {}", position + 1),
+                *span,
+            )
+            .with_label(format!("expected `{expected}`, found `{found}`")),
+            TypeError::ArgumentCount {
+                expected,
+                found,
+                span,
+            } => Diagnostic::error(
+                format!("this function takes {expected} argument(s) but {found} were supplied"),
+                *span,
+            ),
+            TypeError::NotAFunction { ty, span } => {
+                Diagnostic::error(format!("`{ty}` is not a function"), *span)
+                    .with_label("called here")
+            }
+            TypeError::InfiniteType {
+                var,
+                ty,
+                position,
+                span,
+            } => Diagnostic::error(format!("infinite type in argument {}", position + 1), *span)
+                .with_label(format!("`{var}` would have to be `{ty}`, which contains it"))
+                .with_note("a value cannot have a type that contains the type itself"),
+            TypeError::ComponentCount { ty, expected, span } => {
+                let diagnostic = Diagnostic::error(
+                    format!("expected a tuple of {expected} components, found `{ty}`"),
+                    *span,
+                )
+                .with_label(format!("{expected} components assigned here"));
+                match *ty.atom().inner() {
+                    TyInner::Undetermined => diagnostic
+                        .with_note("the type of the tuple must be known here; annotate it, as in `(t: (Int, Int)) => { a, b = t; a }`"),
+                    _ => diagnostic,
+                }
+            }
+            TypeError::NoComponent { ty, index, span } => {
+                let diagnostic =
+                    Diagnostic::error(format!("no component `.{index}` in `{ty}`"), *span);
+                match *ty.atom().inner() {
+                    TyInner::Undetermined => diagnostic
+                        .with_note("the type of the tuple must be known here; annotate it, as in `(t: (Int, Int)) => t.0`"),
+                    _ => diagnostic,
+                }
+            }
+        }
+    }
+}
+
+impl RuntimeError {
+    /// `span` is the expression whose evaluation failed.
+    pub fn to_diagnostic(&self, span: Span) -> Diagnostic {
+        match self {
+            RuntimeError::DivisionByZero => Diagnostic::error("division by zero", span),
+            RuntimeError::Overflow => Diagnostic::error("integer overflow", span)
+                .with_note(format!("integers range from {} to {}", i32::MIN, i32::MAX)),
+            RuntimeError::Uninitialized => {
+                Diagnostic::error("variable read before it is assigned", span)
+            }
+            RuntimeError::InvalidArguments => Diagnostic::error("invalid arguments", span),
+            RuntimeError::NotAFunction => {
+                Diagnostic::error("called a value that is not a function", span)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cyclic_types() {
+        let tys = TyTable::new();
+        let ty = tys.new_ty(TyInner::Undetermined);
+        let func = tys.new_ty(TyInner::Func {
+            args: vec![tys.new_ty(TyInner::Int)],
+            ret: ty.clone(),
+        });
+        ty.bind(&func);
+        assert_eq!(func.to_string(), "(Int)...");
+        assert_eq!(tys.new_ty(TyInner::Ref(ty)).to_string(), "Ref[(Int)...]");
+    }
+}
@@ -0,0 +1,377 @@
+use super::*;
+
+/// A rule of the algorithmic subtyping in `note/lang.tex`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rule {
+    /// `A <: A` for an atom `A`.
+    Atom,
+    /// `Int <: Float`.
+    Int,
+    /// `T <: (S_0 ... S_m) T'` from `T <: T'`.
+    Const,
+    /// `(T_0' ... T_n') T <: (T_0 ... T_n) T'` from `T <: T'` and `T_i <: T_i'`, where the arguments are contravariant.
+    Arrow,
+}
+
+/// A derivation of `sub <: sup`, which ends with `rule` applied to `premises`.
+/// The premises of SA-Arrow are the one for the return types followed by those for the arguments.
+/// A premise is shared with the conversions that `eval` makes by it.
+pub struct Derivation {
+    pub rule: Rule,
+    pub sub: Ty,
+    pub sup: Ty,
+    pub premises: Vec<Rc<Derivation>>,
+}
+
+/// Derives `sub <: sup` with SA-Atom, SA-Int, SA-Const and SA-Arrow, or returns `None` if it does not hold.
+/// When both SA-Arrow and SA-Const apply, SA-Arrow is tried first.
+/// The types outside the grammar of the note (references, tuples and named types) are atoms,
+/// each a subtype only of a type equal to it, and an undetermined type is a subtype only of itself.
+pub fn subtype(sub: &Ty, sup: &Ty) -> Option<Derivation> {
+    derive(sub, sup, false)
+}
+
+/// Relates `sub` and `sup` as `subtype` does, unifying the undetermined types in them so that `sub <: sup` holds.
+/// This is how `unify` relates the arguments and the results of two functions, following SA-Arrow.
+pub(super) fn subsume(sub: &Ty, sup: &Ty) -> Result<(), UnifyError> {
+    derive(sub, sup, true)
+        .ok_or(UnifyError::Mismatch)?
+        .unify_atoms()
+}
+
+/// Derives `sub <: sup` as `subtype` documents.
+/// If `loose`, an undetermined type stands for any type, so that it is related to anything by SA-Atom,
+/// and the derivation holds once `unify_atoms` has made the two sides of each SA-Atom equal.
+fn derive(sub: &Ty, sup: &Ty, loose: bool) -> Option<Derivation> {
+    let (rule, premises) = match (&*sub.inner(), &*sup.inner()) {
+        _ if loose && (sub.is_undetermined() || sup.is_undetermined()) => (Rule::Atom, Vec::new()),
+        (TyInner::Int, TyInner::Float) => (Rule::Int, Vec::new()),
+        (
+            TyInner::Func {
+                args: sub_args,
+                ret: sub_ret,
+            },
+            TyInner::Func {
+                args: sup_args,
+                ret: sup_ret,
+            },
+        ) => {
+            let arrow = || {
+                if sub_args.len() != sup_args.len() {
+                    return None;
+                }
+                iter::once(derive(sub_ret, sup_ret, loose))
+                    .chain(
+                        sup_args
+                            .iter()
+                            .zip(sub_args)
+                            .map(|(sup_arg, sub_arg)| derive(sup_arg, sub_arg, loose)),
+                    )
+                    .map(|premise| premise.map(Rc::new))
+                    .collect()
+            };
+            match arrow() {
+                Some(premises) => (Rule::Arrow, premises),
+                None => (Rule::Const, vec![Rc::new(derive(sub, sup_ret, loose)?)]),
+            }
+        }
+        (_, TyInner::Func { ret: sup_ret, .. }) => {
+            (Rule::Const, vec![Rc::new(derive(sub, sup_ret, loose)?)])
+        }
+        _ if sub.matches(sup, loose) => (Rule::Atom, Vec::new()),
+        _ => return None,
+    };
+    Some(Derivation {
+        rule,
+        sub: sub.clone(),
+        sup: sup.clone(),
+        premises,
+    })
+}
+
+impl Derivation {
+    /// Unifies the two sides of each SA-Atom of a loose derivation, which makes it a derivation by `subtype`.
+    /// A side that an earlier premise has determined since so that the sides can no longer be equal is related again.
+    fn unify_atoms(&self) -> Result<(), UnifyError> {
+        match self.rule {
+            Rule::Atom if self.sub.matches(&self.sup, true) => self.sub.equate(&self.sup),
+            Rule::Atom => subsume(&self.sub, &self.sup),
+            Rule::Int => Ok(()),
+            Rule::Const | Rule::Arrow => self
+                .premises
+                .iter()
+                .try_for_each(|premise| premise.unify_atoms()),
+        }
+    }
+}
+
+impl Ty {
+    /// Whether `self` and `other` are the same type, where undetermined types are the same only if they are one variable.
+    pub(super) fn equals(&self, other: &Ty) -> bool {
+        self.matches(other, false)
+    }
+    /// Whether `self` and `other` are the same type as `equals` decides,
+    /// except that an undetermined type is the same as any type if `loose`.
+    fn matches(&self, other: &Ty, loose: bool) -> bool {
+        if self.same(other) || loose && (self.is_undetermined() || other.is_undetermined()) {
+            return true;
+        }
+        let all_equal = |these: &[Ty], others: &[Ty]| {
+            these.len() == others.len()
+                && these.iter().zip(others).all(|(x, y)| x.matches(y, loose))
+        };
+        match (&*self.inner(), &*other.inner()) {
+            (TyInner::Int, TyInner::Int)
+            | (TyInner::Float, TyInner::Float)
+            | (TyInner::Bool, TyInner::Bool)
+            | (TyInner::Str, TyInner::Str) => true,
+            (TyInner::Named { id: x, .. }, TyInner::Named { id: y, .. }) => x == y,
+            (TyInner::Ref(x), TyInner::Ref(y)) => x.matches(y, loose),
+            (TyInner::Tuple(xs), TyInner::Tuple(ys)) => all_equal(xs, ys),
+            (TyInner::Func { args: xs, ret: x }, TyInner::Func { args: ys, ret: y }) => {
+                all_equal(xs, ys) && x.matches(y, loose)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A type of the grammar in `note/lang.tex`, over the atoms `Int`, `Float` and `Bool`.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    enum T {
+        Atom(usize),
+        Func(Vec<T>, Box<T>),
+    }
+
+    impl T {
+        fn to_ty(&self, table: &TyTable) -> Ty {
+            match self {
+                T::Atom(0) => table.new_ty(TyInner::Int),
+                T::Atom(1) => table.new_ty(TyInner::Float),
+                T::Atom(_) => table.new_ty(TyInner::Bool),
+                T::Func(args, ret) => table.new_ty(TyInner::Func {
+                    args: args.iter().map(|arg| arg.to_ty(table)).collect(),
+                    ret: ret.to_ty(table),
+                }),
+            }
+        }
+    }
+
+    /// Every type with at most `size` nodes whose functions take one or two arguments.
+    /// It contains every part of each type in it.
+    fn types(size: usize) -> Vec<T> {
+        // `by_size[n]` are the types of exactly `n` nodes.
+        let mut by_size: Vec<Vec<T>> = vec![Vec::new(); size + 1];
+        by_size[1] = (0..3).map(T::Atom).collect();
+        for n in 2..=size {
+            let mut tys = Vec::new();
+            // One node for the function, and the rest for its arguments and return type.
+            for ret_size in 1..n - 1 {
+                for ret in by_size[ret_size].clone() {
+                    for args in sequences(&by_size, n - 1 - ret_size, 2) {
+                        tys.push(T::Func(args, Box::new(ret.clone())));
+                    }
+                }
+            }
+            by_size[n] = tys;
+        }
+        by_size.concat()
+    }
+
+    /// Every sequence of one to `max_len` types whose sizes add up to `size`.
+    fn sequences(by_size: &[Vec<T>], size: usize, max_len: usize) -> Vec<Vec<T>> {
+        let mut ret = Vec::new();
+        for first_size in 1..=size {
+            for first in &by_size[first_size] {
+                if first_size == size {
+                    ret.push(vec![first.clone()]);
+                } else if max_len > 1 {
+                    for rest in sequences(by_size, size - first_size, max_len - 1) {
+                        ret.push(iter::once(first.clone()).chain(rest).collect());
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// The pairs in `tys` related by S-Refl, S-Trans, S-Const, S-Arrow and S-Int, found by saturating the relation.
+    /// Only the types in `tys` may appear in a derivation, so this may miss pairs that need larger types in between.
+    fn declarative(tys: &[T]) -> Vec<Vec<bool>> {
+        let n = tys.len();
+        let mut rel = vec![vec![false; n]; n];
+        for (i, sub) in tys.iter().enumerate() {
+            for (j, sup) in tys.iter().enumerate() {
+                rel[i][j] = sub == sup
+                    || (sub, sup) == (&T::Atom(0), &T::Atom(1))
+                    || matches!(sup, T::Func(_, ret) if **ret == *sub);
+            }
+        }
+        let index = |ty: &T| tys.iter().position(|other| other == ty).unwrap();
+        loop {
+            let mut changed = false;
+            for (i, sub) in tys.iter().enumerate() {
+                for (j, sup) in tys.iter().enumerate() {
+                    let (T::Func(sub_args, sub_ret), T::Func(sup_args, sup_ret)) = (sub, sup)
+                    else {
+                        continue;
+                    };
+                    if !rel[i][j]
+                        && sub_args.len() == sup_args.len()
+                        && rel[index(sub_ret)][index(sup_ret)]
+                        && sub_args
+                            .iter()
+                            .zip(sup_args)
+                            .all(|(sub_arg, sup_arg)| rel[index(sup_arg)][index(sub_arg)])
+                    {
+                        rel[i][j] = true;
+                        changed = true;
+                    }
+                }
+            }
+            // S-Trans, closed by Warshall's algorithm.
+            for k in 0..n {
+                let from_k = rel[k].clone();
+                for row in rel.iter_mut().filter(|row| row[k]) {
+                    for (to, &from) in row.iter_mut().zip(&from_k) {
+                        if from && !*to {
+                            *to = true;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed {
+                return rel;
+            }
+        }
+    }
+
+    /// Checks that each step of `derivation` follows its rule.
+    fn check(derivation: &Derivation) {
+        let premise = |i: usize| {
+            let premise: &Derivation = &derivation.premises[i];
+            check(premise);
+            (premise.sub.clone(), premise.sup.clone())
+        };
+        let (sub, sup) = (&derivation.sub, &derivation.sup);
+        match derivation.rule {
+            Rule::Atom => {
+                assert!(derivation.premises.is_empty());
+                assert!(sub.equals(sup));
+                assert!(!matches!(*sub.inner(), TyInner::Func { .. }));
+            }
+            Rule::Int => {
+                assert!(derivation.premises.is_empty());
+                assert!(matches!(*sub.inner(), TyInner::Int));
+                assert!(matches!(*sup.inner(), TyInner::Float));
+            }
+            Rule::Const => {
+                let (_, sup_ret) = sup.get_args_ret().unwrap();
+                assert_eq!(derivation.premises.len(), 1);
+                let (premise_sub, premise_sup) = premise(0);
+                assert!(premise_sub.equals(sub) && premise_sup.equals(&sup_ret));
+            }
+            Rule::Arrow => {
+                let (sub_args, sub_ret) = sub.get_args_ret().unwrap();
+                let (sup_args, sup_ret) = sup.get_args_ret().unwrap();
+                assert_eq!(derivation.premises.len(), 1 + sub_args.len());
+                assert_eq!(sub_args.len(), sup_args.len());
+                let (ret_sub, ret_sup) = premise(0);
+                assert!(ret_sub.equals(&sub_ret) && ret_sup.equals(&sup_ret));
+                for (i, (sub_arg, sup_arg)) in sub_args.iter().zip(&sup_args).enumerate() {
+                    let (arg_sub, arg_sup) = premise(1 + i);
+                    assert!(arg_sub.equals(sup_arg) && arg_sup.equals(sub_arg));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn agrees_with_declarative_rules() {
+        let table = TyTable::new();
+        let tys = types(6);
+        let rel = declarative(&tys);
+        let converted: Vec<_> = tys.iter().map(|ty| ty.to_ty(&table)).collect();
+        for (i, sub) in converted.iter().enumerate() {
+            for (j, sup) in converted.iter().enumerate() {
+                let derivation = subtype(sub, sup);
+                assert_eq!(
+                    derivation.is_some(),
+                    rel[i][j],
+                    "{sub} <: {sup} by the algorithmic rules is {}",
+                    derivation.is_some()
+                );
+                if let Some(derivation) = derivation {
+                    check(&derivation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unify_agrees_with_subtype() {
+        let table = TyTable::new();
+        let tys = types(6);
+        for sub in &tys {
+            for sup in &tys {
+                let (found, expected) = (sub.to_ty(&table), sup.to_ty(&table));
+                // `unify` lifts `found` over the outer levels that `expected` lacks, and relates the rest by subtyping.
+                let lifted = found.levels().0.saturating_sub(expected.levels().0);
+                let mut ret = found.clone();
+                for _ in 0..lifted {
+                    ret = ret.ret().unwrap();
+                }
+                assert_eq!(
+                    found.unify(&expected).ok().map(|lifts| lifts.len()),
+                    subtype(&ret, &expected).map(|_| lifted),
+                    "{found} passed where {expected} is expected"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn undetermined_types_are_unified() {
+        let table = TyTable::new();
+        let var = table.new_ty(TyInner::Undetermined);
+        let identity = table.new_ty(TyInner::Func {
+            args: vec![var.clone()],
+            ret: var.clone(),
+        });
+        let int_to_float = T::Func(vec![T::Atom(0)], Box::new(T::Atom(1))).to_ty(&table);
+        assert!(identity
+            .unify(&int_to_float)
+            .is_ok_and(|lifts| lifts.is_empty()));
+        assert!(var.equals(&T::Atom(1).to_ty(&table)));
+        let float_to_int = T::Func(vec![T::Atom(1)], Box::new(T::Atom(0))).to_ty(&table);
+        assert!(identity.unify(&float_to_int).is_err());
+    }
+
+    #[test]
+    fn arguments_are_contravariant() {
+        let table = TyTable::new();
+        let float_to_int = T::Func(vec![T::Atom(1)], Box::new(T::Atom(0))).to_ty(&table);
+        let int_to_float = T::Func(vec![T::Atom(0)], Box::new(T::Atom(1))).to_ty(&table);
+        let derivation = subtype(&float_to_int, &int_to_float).unwrap();
+        assert_eq!(derivation.rule, Rule::Arrow);
+        assert_eq!(derivation.premises[0].rule, Rule::Int);
+        assert_eq!(derivation.premises[1].rule, Rule::Int);
+        assert!(subtype(&int_to_float, &float_to_int).is_none());
+    }
+
+    #[test]
+    fn constant_functions() {
+        let table = TyTable::new();
+        let int = T::Atom(0).to_ty(&table);
+        let bool_to_int = T::Func(vec![T::Atom(2)], Box::new(T::Atom(0))).to_ty(&table);
+        let derivation = subtype(&int, &bool_to_int).unwrap();
+        assert_eq!(derivation.rule, Rule::Const);
+        assert_eq!(derivation.premises[0].rule, Rule::Atom);
+        assert!(subtype(&bool_to_int, &int).is_none());
+    }
+}
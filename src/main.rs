@@ -1,30 +1,98 @@
 mod ast;
+mod cli;
 mod context;
+mod diagnostic;
 mod ir;
 mod parser;
+mod repl;
+mod session;
+mod span;
 
-use std::{cell::RefCell, io::BufRead, rc::Rc};
-
-fn main() {
-    let source = std::io::stdin().lock();
-    let mut context = context::Context::new();
-    let mut exprs: Vec<_> = source
-        .lines()
-        .filter_map(|line| {
-            let line = line.expect("failed to read from stdin");
-            parser::parse(&line).map(|expr| context.translate_expr(expr))
-        })
-        .collect();
-    let vars: Vec<_> = (0..context.num_variables())
-        .map(|_| {
-            (
-                ir::Ty::new(ir::TyInner::Ref(ir::Ty::new(ir::TyInner::Undetermined))),
-                ir::Value::Var(Rc::new(RefCell::new(None))),
-            )
-        })
-        .collect();
-    for expr in &mut exprs {
-        let (ty, value) = expr.eval(&vars);
-        println!("{expr}: {ty}\n  -> {value}");
+use cli::Stage;
+use session::{Mode, Session};
+use std::{io::Read, process::ExitCode};
+
+fn main() -> ExitCode {
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{}", cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let success = match command {
+        cli::Command::Run { files, emit } => process_files(&files, Mode::Run, &emit),
+        cli::Command::Check { files, emit } => process_files(&files, Mode::Check, &emit),
+        cli::Command::Repl => repl::run(),
+        cli::Command::Help => {
+            println!("{}", cli::USAGE);
+            true
+        }
+    };
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Processes every file, even after one fails. Returns whether all of them had no error.
+fn process_files(files: &[String], mode: Mode, emit: &[Stage]) -> bool {
+    let mut success = true;
+    for file in files {
+        success &= process_file(file, mode, emit);
+    }
+    success
+}
+
+/// Reads `path`, or standard input if it is `-`, and processes it. Returns whether it had no error.
+fn process_file(path: &str, mode: Mode, emit: &[Stage]) -> bool {
+    let mut source = String::new();
+    let (name, result) = if path == "-" {
+        ("<stdin>", std::io::stdin().read_to_string(&mut source))
+    } else {
+        (
+            path,
+            std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut source)),
+        )
+    };
+    if let Err(err) = result {
+        eprintln!("error: cannot read `{path}`: {err}");
+        return false;
+    }
+    process(name, &source, mode, emit)
+}
+
+/// Processes `source`, printing every diagnostic to standard error.
+/// Returns whether there was no error.
+fn process(name: &str, source: &str, mode: Mode, emit: &[Stage]) -> bool {
+    let mut session = Session::new(name, mode, emit);
+    session.process(source);
+    session.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_file_is_processed_even_after_an_error() {
+        let dir = std::env::temp_dir().join(format!("comp-main-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("the directory must be made");
+        let good = dir.join("good");
+        let bad = dir.join("bad");
+        std::fs::write(&good, "x = 1\n").expect("the file must be written");
+        std::fs::write(&bad, "x = 1 + true\n").expect("the file must be written");
+        let path = |path: &std::path::Path| path.to_string_lossy().into_owned();
+        assert!(process_files(&[path(&good)], Mode::Check, &[]));
+        assert!(!process_files(&[path(&bad), path(&good)], Mode::Check, &[]));
+        assert!(!process_files(&[path(&dir.join("missing")), path(&good)], Mode::Check, &[]));
+        std::fs::remove_dir_all(&dir).expect("the directory must be removed");
+    }
+
+    #[test]
+    fn each_file_has_its_own_variables() {
+        assert!(process("a", "x = 1", Mode::Check, &[]));
+        assert!(!process("b", "y = x", Mode::Check, &[]));
     }
 }
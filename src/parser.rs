@@ -1,152 +1,1065 @@
 mod token;
-use crate::ast::{BinOp, Expr, Ty};
+use crate::ast::{
+    Arm, BinOp, CtorDecl, Expr, ExprKind, Param, Pattern, PatternKind, Stmt, Ty, TyKind, UnOp,
+};
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
 use enum_iterator::Sequence;
-use token::{Lexer, Token};
+pub use token::Token;
+use token::{Lexer, TokenKind};
 
-pub fn parse(input: &str) -> Option<Expr> {
-    let mut lexer = Lexer::new(input);
-    let ret = parse_assign(&mut lexer);
-    if let Some(token) = lexer.next_token {
-        panic!("unexpected token {token:?}");
+pub enum ParseError {
+    UnexpectedCharacter(char, Span),
+    UnexpectedToken(TokenKind, Span),
+    IntegerOutOfRange(Span),
+    FloatOutOfRange(Span),
+    UnterminatedString(Span),
+    InvalidEscape(Span),
+    Unclosed {
+        opening: Span,
+        expected: TokenKind,
+        found: Span,
+    },
+    EmptyParentheses(Span),
+    EmptyBrackets(Span),
+    EmptyArgument(Span),
+    MissingOperand(Span),
+    MissingRightHandSide(Span),
+    MissingReturnType(Span),
+    MissingType(Span),
+    MissingBody(Span),
+    InvalidParameter(Span),
+    UnexpectedAnnotation(Span),
+    MissingIndex(Span),
+    InvalidPattern(Span),
+    MissingName(Span),
+    MissingInitializer(Span),
+    MissingBlockValue(Span),
+    MissingExpression(Span),
+    Expected(TokenKind, Span),
+}
+
+/// Splits `input` into tokens, skipping unexpected characters.
+/// `line` is the line number where `input` starts within the whole source.
+pub fn tokenize(input: &str, line: usize) -> Vec<Token> {
+    let mut lexer = Lexer::new(input, line);
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next_token.take() {
+        tokens.push(token);
+        lexer.consume_token();
+    }
+    tokens
+}
+
+/// Whether `input` stops in the middle of a statement, inside an open group or after an operator.
+pub fn needs_more_input(input: &str) -> bool {
+    Lexer::needs_more_input(input)
+}
+
+/// Parses the statements in `input`, which are separated by newlines or `;`.
+/// Returns the statements without syntax errors, and every syntax error found in the others.
+/// `line` is the line number where `input` starts within the whole source.
+pub fn parse(input: &str, line: usize) -> (Vec<Stmt>, Vec<ParseError>) {
+    let mut lexer = Lexer::new(input, line);
+    let mut statements = Vec::new();
+    loop {
+        statements.extend(parse_statements(&mut lexer));
+        let Some(ref token) = lexer.next_token else {
+            return (statements, lexer.errors);
+        };
+        // A `}` without the matching `{`.
+        let err = ParseError::UnexpectedToken(token.kind.clone(), token.span);
+        lexer.errors.push(err);
+        lexer.consume_token();
+    }
+}
+
+/// Parses statements separated by newlines or `;` up to the end of the input or a `}`.
+/// A statement with a syntax error is recorded and dropped.
+fn parse_statements(lexer: &mut Lexer) -> Vec<Stmt> {
+    let mut statements = Vec::new();
+    loop {
+        while let Some(TokenKind::Newline | TokenKind::Semicolon) = lexer.next_kind() {
+            lexer.consume_token();
+        }
+        if let None | Some(TokenKind::ClosingBrace) = lexer.next_kind() {
+            return statements;
+        }
+        let num_errors = lexer.errors_before_next;
+        match parse_statement(lexer) {
+            Ok(statement) => match lexer.next_token {
+                None
+                | Some(Token {
+                    kind: TokenKind::Newline | TokenKind::Semicolon | TokenKind::ClosingBrace,
+                    ..
+                }) => {
+                    if lexer.errors.len() == num_errors {
+                        statements.extend(statement);
+                    }
+                }
+                Some(ref token) => {
+                    let err = ParseError::UnexpectedToken(token.kind.clone(), token.span);
+                    lexer.errors.push(err);
+                    skip_statement(lexer);
+                }
+            },
+            Err(err) => {
+                lexer.errors.push(err);
+                skip_statement(lexer);
+            }
+        }
+        // Any later error in the statement is likely to follow from the first, as from a character that was skipped.
+        lexer.errors.truncate(num_errors + 1);
+    }
+}
+
+/// Skips tokens up to the end of the current statement, which is a newline, `;` or `}` not nested in another group.
+fn skip_statement(lexer: &mut Lexer) {
+    let mut depth = 0_usize;
+    while let Some(kind) = lexer.next_kind() {
+        match kind {
+            TokenKind::Newline | TokenKind::Semicolon | TokenKind::ClosingBrace if depth == 0 => {
+                return
+            }
+            TokenKind::OpeningParenthesis
+            | TokenKind::OpeningBracket
+            | TokenKind::OpeningBrace => depth += 1,
+            TokenKind::ClosingParenthesis
+            | TokenKind::ClosingBracket
+            | TokenKind::ClosingBrace => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        lexer.consume_token();
+    }
+}
+
+/// Parses a `let` or `type` statement, or an expression.
+fn parse_statement(lexer: &mut Lexer) -> Result<Option<Stmt>, ParseError> {
+    match lexer.next_kind() {
+        Some(TokenKind::Let) => parse_let(lexer).map(Some),
+        Some(TokenKind::Type) => parse_type_decl(lexer).map(Some),
+        _ => Ok(parse_assign(lexer, true)?.map(Stmt::Expr)),
+    }
+}
+
+/// Consumes the name that must come next. `keyword` is where the construct that needs it starts.
+fn parse_name(lexer: &mut Lexer, keyword: Span) -> Result<(String, Span), ParseError> {
+    let span = lexer.next_span();
+    let Some(TokenKind::Identifier(name)) = lexer.next_token.as_mut().map(|token| &mut token.kind)
+    else {
+        return Err(ParseError::MissingName(keyword.to(span)));
+    };
+    let name = std::mem::take(name);
+    lexer.consume_token();
+    Ok((name, span))
+}
+
+/// Parses `let NAME [: TYPE] = EXPR`.
+fn parse_let(lexer: &mut Lexer) -> Result<Stmt, ParseError> {
+    let start = lexer.next_span();
+    lexer.consume_token();
+    let (name, name_span) = parse_name(lexer, start)?;
+    let mut param = Param {
+        name,
+        ty: None,
+        span: name_span,
+    };
+    if let Some(TokenKind::Colon) = lexer.next_kind() {
+        let colon = lexer.next_span();
+        lexer.consume_token();
+        param.ty = Some(
+            parse_ty(lexer)?
+                .ok_or_else(|| ParseError::MissingType(colon.to(lexer.next_span())))?,
+        );
     }
-    ret
+    let Some(TokenKind::Equal) = lexer.next_kind() else {
+        return Err(ParseError::MissingInitializer(start.to(lexer.next_span())));
+    };
+    let equal = lexer.next_span();
+    lexer.consume_token();
+    let value = parse_assign(lexer, true)?
+        .ok_or_else(|| ParseError::MissingRightHandSide(equal.to(lexer.next_span())))?;
+    Ok(Stmt::Let {
+        param,
+        span: start.to(value.span),
+        value,
+    })
+}
+
+/// Parses `type NAME = CTOR | CTOR ...`, where each `CTOR` is a name optionally followed by its field types in parentheses.
+fn parse_type_decl(lexer: &mut Lexer) -> Result<Stmt, ParseError> {
+    let start = lexer.next_span();
+    lexer.consume_token();
+    let (name, _) = parse_name(lexer, start)?;
+    expect(lexer, TokenKind::Equal)?;
+    let mut ctors = Vec::new();
+    loop {
+        let (ctor_name, mut span) = parse_name(lexer, lexer.next_span())?;
+        let mut fields = Vec::new();
+        if let Some(TokenKind::OpeningParenthesis) = lexer.next_kind() {
+            let opening = lexer.next_span();
+            lexer.consume_token();
+            // On a syntax error, which is already recorded, the statement is dropped anyway.
+            if let Some((tys, end)) =
+                parse_group(lexer, opening, TokenKind::ClosingParenthesis, |lexer| {
+                    Ok(parse_list(lexer, parse_ty))
+                })
+            {
+                fields = tys;
+                span = span.to(end);
+            }
+        }
+        ctors.push(CtorDecl {
+            name: ctor_name,
+            fields,
+            span,
+        });
+        if let Some(TokenKind::VerticalLine) = lexer.next_kind() {
+            lexer.consume_token();
+        } else {
+            break;
+        }
+    }
+    Ok(Stmt::Type {
+        name,
+        span: start.to(ctors[ctors.len() - 1].span),
+        ctors,
+    })
 }
 
-fn parse_assign(lexer: &mut Lexer) -> Option<Expr> {
-    let left_hand_side = parse_binary_operation(lexer)?;
-    if let Some(Token::Equal) = lexer.next_token {
+/// Parses an assignment or an expression.
+/// If `tuples` is true, either side may be a tuple without parentheses, as in `a, b = b, a`.
+/// Lambda bodies do not allow it, so that `f((a) => a, 1)` has two arguments.
+fn parse_assign(lexer: &mut Lexer, tuples: bool) -> Result<Option<Expr>, ParseError> {
+    let left_hand_side = if tuples {
+        parse_bare_tuple(lexer)?
+    } else {
+        parse_binary_operation(lexer)?
+    };
+    let Some(left_hand_side) = left_hand_side else {
+        return Ok(None);
+    };
+    if let Some(TokenKind::Equal) = lexer.next_kind() {
+        let equal = lexer.next_span();
         lexer.consume_token();
-        let right_hand_side = parse_assign(lexer).expect("empty right hand side");
-        Some(Expr::Assign(
-            Box::new(left_hand_side),
-            Box::new(right_hand_side),
-        ))
+        let right_hand_side = parse_assign(lexer, tuples)?
+            .ok_or_else(|| ParseError::MissingRightHandSide(equal.to(lexer.next_span())))?;
+        Ok(Some(Expr {
+            span: left_hand_side.span.to(right_hand_side.span),
+            kind: ExprKind::Assign(Box::new(left_hand_side), Box::new(right_hand_side)),
+        }))
     } else {
-        Some(left_hand_side)
+        Ok(Some(left_hand_side))
     }
 }
 
-fn parse_binary_operation(lexer: &mut Lexer) -> Option<Expr> {
+/// Parses expressions separated by commas, which make a tuple if there are two or more.
+fn parse_bare_tuple(lexer: &mut Lexer) -> Result<Option<Expr>, ParseError> {
+    let Some(first) = parse_binary_operation(lexer)? else {
+        return Ok(None);
+    };
+    let mut items = vec![first];
+    while let Some(TokenKind::Comma) = lexer.next_kind() {
+        let comma = lexer.next_span();
+        lexer.consume_token();
+        items.push(
+            parse_binary_operation(lexer)?
+                .ok_or_else(|| ParseError::MissingOperand(comma.to(lexer.next_span())))?,
+        );
+    }
+    if items.len() == 1 {
+        return Ok(items.pop());
+    }
+    Ok(Some(Expr {
+        span: items[0].span.to(items[items.len() - 1].span),
+        kind: ExprKind::Tuple(items),
+    }))
+}
+
+fn parse_binary_operation(lexer: &mut Lexer) -> Result<Option<Expr>, ParseError> {
     parse_binary_operation_rec(lexer, Precedence::first())
 }
 
-fn parse_binary_operation_rec(lexer: &mut Lexer, precedence: Option<Precedence>) -> Option<Expr> {
+fn parse_binary_operation_rec(
+    lexer: &mut Lexer,
+    precedence: Option<Precedence>,
+) -> Result<Option<Expr>, ParseError> {
     let Some(precedence) = precedence else {
-        return parse_factor(lexer);
+        return parse_unary(lexer);
     };
-    let mut left = parse_binary_operation_rec(lexer, precedence.next());
+    let mut left = parse_binary_operation_rec(lexer, precedence.next())?;
     while let Some(operator) = lexer
-        .next_token
-        .as_ref()
+        .next_kind()
         .and_then(|token| binary_operator(token, precedence))
     {
+        let operator_span = lexer.next_span();
+        let left_operand = left.ok_or(ParseError::MissingOperand(operator_span))?;
         lexer.consume_token();
-        let right = parse_binary_operation_rec(lexer, precedence.next());
-        left = Some(Expr::Bin(
-            Box::new(left.expect("empty left operand")),
-            operator,
-            Box::new(right.expect("empty right operand")),
-        ));
+        let right_operand = parse_binary_operation_rec(lexer, precedence.next())?
+            .ok_or_else(|| ParseError::MissingOperand(operator_span.to(lexer.next_span())))?;
+        left = Some(Expr {
+            span: left_operand.span.to(right_operand.span),
+            kind: ExprKind::Bin(Box::new(left_operand), operator, Box::new(right_operand)),
+        });
     }
-    left
+    Ok(left)
 }
 
+/// The levels of binary operators, loosest first.
 #[derive(Clone, Copy, Sequence)]
 enum Precedence {
+    Or,
+    And,
+    Comparison,
     AddSub,
     MulDivRem,
 }
 
-fn binary_operator(token: &Token, precedence: Precedence) -> Option<BinOp> {
+fn binary_operator(token: &TokenKind, precedence: Precedence) -> Option<BinOp> {
     match (token, precedence) {
-        (Token::Asterisk, Precedence::MulDivRem) => Some(BinOp::Mul),
-        (Token::Slash, Precedence::MulDivRem) => Some(BinOp::Div),
-        (Token::Percent, Precedence::MulDivRem) => Some(BinOp::Rem),
-        (Token::Plus, Precedence::AddSub) => Some(BinOp::Add),
-        (Token::Hyphen, Precedence::AddSub) => Some(BinOp::Sub),
+        (TokenKind::DoubleVerticalLine, Precedence::Or) => Some(BinOp::Or),
+        (TokenKind::DoubleAmpersand, Precedence::And) => Some(BinOp::And),
+        (TokenKind::DoubleEqual, Precedence::Comparison) => Some(BinOp::Eq),
+        (TokenKind::ExclamationEqual, Precedence::Comparison) => Some(BinOp::Ne),
+        (TokenKind::Less, Precedence::Comparison) => Some(BinOp::Lt),
+        (TokenKind::LessEqual, Precedence::Comparison) => Some(BinOp::Le),
+        (TokenKind::Greater, Precedence::Comparison) => Some(BinOp::Gt),
+        (TokenKind::GreaterEqual, Precedence::Comparison) => Some(BinOp::Ge),
+        (TokenKind::Asterisk, Precedence::MulDivRem) => Some(BinOp::Mul),
+        (TokenKind::Slash, Precedence::MulDivRem) => Some(BinOp::Div),
+        (TokenKind::Percent, Precedence::MulDivRem) => Some(BinOp::Rem),
+        (TokenKind::Plus, Precedence::AddSub) => Some(BinOp::Add),
+        (TokenKind::Hyphen, Precedence::AddSub) => Some(BinOp::Sub),
         _ => None,
     }
 }
 
-fn parse_factor(lexer: &mut Lexer) -> Option<Expr> {
-    let mut expr = match lexer.next_token.as_mut() {
-        Some(Token::Integer(value)) => {
-            let value = value.parse().unwrap();
+/// Parses prefix operators, which bind tighter than any binary operator.
+fn parse_unary(lexer: &mut Lexer) -> Result<Option<Expr>, ParseError> {
+    let op = match lexer.next_kind() {
+        Some(TokenKind::Hyphen) => UnOp::Neg,
+        Some(TokenKind::Exclamation) => UnOp::Not,
+        _ => return parse_factor(lexer),
+    };
+    let start = lexer.next_span();
+    lexer.consume_token();
+    if let (UnOp::Neg, Some(token @ (TokenKind::Integer(_) | TokenKind::Float(_)))) =
+        (&op, lexer.next_kind())
+    {
+        // A negative literal rather than a negated one, so that `-2147483648` is in range.
+        let span = start.to(lexer.next_span());
+        let kind = number(token, "-", span)?;
+        lexer.consume_token();
+        return Ok(Some(Expr { kind, span }));
+    }
+    let operand = parse_unary(lexer)?
+        .ok_or_else(|| ParseError::MissingOperand(start.to(lexer.next_span())))?;
+    Ok(Some(Expr {
+        span: start.to(operand.span),
+        kind: ExprKind::Unary(op, Box::new(operand)),
+    }))
+}
+
+/// Skips tokens up to the next `,`, `)`, `]` or `}` that is not nested in another group.
+fn skip_to_separator(lexer: &mut Lexer) {
+    let mut depth = 0;
+    while let Some(kind) = lexer.next_kind() {
+        match kind {
+            TokenKind::OpeningParenthesis
+            | TokenKind::OpeningBracket
+            | TokenKind::OpeningBrace => depth += 1,
+            TokenKind::Comma
+            | TokenKind::ClosingParenthesis
+            | TokenKind::ClosingBracket
+            | TokenKind::ClosingBrace
+                if depth == 0 =>
+            {
+                return
+            }
+            TokenKind::ClosingParenthesis
+            | TokenKind::ClosingBracket
+            | TokenKind::ClosingBrace => depth -= 1,
+            _ => {}
+        }
+        lexer.consume_token();
+    }
+}
+
+/// Parses the inside of a group opened at `opening` with `parse_inner`, then consumes the `closing` token.
+/// On a syntax error the error is recorded, the rest of the group is skipped and `None` is returned.
+fn parse_group<T>(
+    lexer: &mut Lexer,
+    opening: Span,
+    closing: TokenKind,
+    parse_inner: impl FnOnce(&mut Lexer) -> Result<T, ParseError>,
+) -> Option<(T, Span)> {
+    let num_errors = lexer.errors.len();
+    let mut inner = parse_inner(lexer).map_err(|err| lexer.errors.push(err)).ok();
+    loop {
+        let Some(ref token) = lexer.next_token else {
+            if lexer.errors.len() == num_errors {
+                lexer.errors.push(ParseError::Unclosed {
+                    opening,
+                    expected: closing.clone(),
+                    found: lexer.next_span(),
+                });
+            }
+            return None;
+        };
+        if std::mem::discriminant(&token.kind) == std::mem::discriminant(&closing) {
+            let end = token.span;
             lexer.consume_token();
-            Expr::Int(value)
+            return inner.map(|inner| (inner, end));
+        }
+        if lexer.errors.len() == num_errors {
+            lexer.errors.push(ParseError::Unclosed {
+                opening,
+                expected: closing.clone(),
+                found: token.span,
+            });
         }
-        Some(Token::Identifier(name)) => {
+        inner = None;
+        lexer.consume_token();
+        skip_to_separator(lexer);
+    }
+}
+
+/// Parses comma-separated items up to (but not including) the closing token.
+/// A trailing comma is allowed. An item with a syntax error is recorded and skipped.
+fn parse_list<T>(
+    lexer: &mut Lexer,
+    parse_item: fn(&mut Lexer) -> Result<Option<T>, ParseError>,
+) -> Vec<T> {
+    let mut items = Vec::new();
+    loop {
+        match parse_item(lexer) {
+            Ok(Some(item)) => items.push(item),
+            Ok(None) => {
+                if let Some(TokenKind::Comma) = lexer.next_kind() {
+                    lexer
+                        .errors
+                        .push(ParseError::EmptyArgument(lexer.next_span()));
+                }
+            }
+            Err(err) => {
+                lexer.errors.push(err);
+                skip_to_separator(lexer);
+            }
+        }
+        if let Some(TokenKind::Comma) = lexer.next_kind() {
+            lexer.consume_token();
+        } else {
+            return items;
+        }
+    }
+}
+
+fn parse_factor(lexer: &mut Lexer) -> Result<Option<Expr>, ParseError> {
+    let Some(start) = lexer.next_token.as_ref().map(|token| token.span) else {
+        return Ok(None);
+    };
+    let mut expr = match lexer.next_token.as_mut().map(|token| &mut token.kind) {
+        Some(token @ (TokenKind::Integer(_) | TokenKind::Float(_))) => {
+            let kind = number(token, "", start)?;
+            lexer.consume_token();
+            Expr { kind, span: start }
+        }
+        Some(TokenKind::Identifier(name)) => {
             let name = std::mem::take(name);
             lexer.consume_token();
-            Expr::Var(name)
+            Expr {
+                kind: ExprKind::Var(name),
+                span: start,
+            }
         }
-        Some(Token::OpeningParenthesis) => {
+        Some(TokenKind::Str(value)) => {
+            let value = std::mem::take(value);
             lexer.consume_token();
-            let expr = parse_binary_operation(lexer).expect("empty parentheses");
-            assert!(matches!(lexer.next_token, Some(Token::ClosingParenthesis)));
+            Expr {
+                kind: ExprKind::Str(value),
+                span: start,
+            }
+        }
+        Some(kind @ (TokenKind::True | TokenKind::False)) => {
+            let value = matches!(kind, TokenKind::True);
             lexer.consume_token();
-            expr
+            Expr {
+                kind: ExprKind::Bool(value),
+                span: start,
+            }
         }
-        Some(Token::OpeningBracket) => {
+        Some(TokenKind::If) => {
             lexer.consume_token();
-            let ty = parse_ty(lexer).expect("empty brackets");
-            assert!(matches!(lexer.next_token, Some(Token::ClosingBracket)));
+            let cond = parse_operand(lexer, start)?;
+            expect(lexer, TokenKind::Then)?;
+            let then_branch = parse_operand(lexer, start)?;
+            expect(lexer, TokenKind::Else)?;
+            let else_branch = parse_operand(lexer, start)?;
+            Expr {
+                span: start.to(else_branch.span),
+                kind: ExprKind::If(
+                    Box::new(cond),
+                    Box::new(then_branch),
+                    Box::new(else_branch),
+                ),
+            }
+        }
+        Some(TokenKind::OpeningParenthesis) => {
             lexer.consume_token();
-            Expr::Id(ty)
+            let items = parse_group(lexer, start, TokenKind::ClosingParenthesis, |lexer| {
+                Ok(parse_list(lexer, parse_annotated))
+            });
+            if let Some(TokenKind::FatArrow) = lexer.next_kind() {
+                let arrow = lexer.next_span();
+                lexer.consume_token();
+                let body = parse_assign(lexer, false)?
+                    .ok_or_else(|| ParseError::MissingBody(arrow.to(lexer.next_span())))?;
+                let span = start.to(body.span);
+                let Some((items, _)) = items else {
+                    return Ok(Some(Expr {
+                        kind: ExprKind::Error,
+                        span,
+                    }));
+                };
+                let params = items
+                    .into_iter()
+                    .filter_map(|(expr, ty)| match expr.kind {
+                        ExprKind::Var(name) => Some(Param {
+                            name,
+                            ty,
+                            span: expr.span,
+                        }),
+                        _ => {
+                            lexer.errors.push(ParseError::InvalidParameter(expr.span));
+                            None
+                        }
+                    })
+                    .collect();
+                Expr {
+                    kind: ExprKind::Lambda {
+                        params,
+                        body: Box::new(body),
+                    },
+                    span,
+                }
+            } else {
+                match items {
+                    Some((items, end)) => {
+                        if let Some((_, Some(ty))) = items.iter().find(|(_, ty)| ty.is_some()) {
+                            return Err(ParseError::UnexpectedAnnotation(ty.span));
+                        }
+                        let mut items: Vec<_> = items.into_iter().map(|(expr, _)| expr).collect();
+                        let kind = match items.len() {
+                            0 => return Err(ParseError::EmptyParentheses(start.to(end))),
+                            1 => items.pop().unwrap().kind,
+                            _ => ExprKind::Tuple(items),
+                        };
+                        Expr {
+                            kind,
+                            span: start.to(end),
+                        }
+                    }
+                    None => Expr {
+                        kind: ExprKind::Error,
+                        span: start.to(lexer.next_span()),
+                    },
+                }
+            }
         }
-        _ => return None,
+        Some(TokenKind::Match) => {
+            lexer.consume_token();
+            let scrutinee = parse_operand(lexer, start)?;
+            let opening = lexer.next_span();
+            expect(lexer, TokenKind::OpeningBrace)?;
+            match parse_group(lexer, opening, TokenKind::ClosingBrace, parse_arms) {
+                Some((arms, end)) => Expr {
+                    kind: ExprKind::Match {
+                        scrutinee: Box::new(scrutinee),
+                        arms,
+                    },
+                    span: start.to(end),
+                },
+                None => Expr {
+                    kind: ExprKind::Error,
+                    span: start.to(lexer.next_span()),
+                },
+            }
+        }
+        Some(TokenKind::OpeningBracket) => {
+            lexer.consume_token();
+            match parse_group(lexer, start, TokenKind::ClosingBracket, |lexer| {
+                parse_ty(lexer)?
+                    .ok_or_else(|| ParseError::EmptyBrackets(start.to(lexer.next_span())))
+            }) {
+                Some((ty, end)) => Expr {
+                    kind: ExprKind::Id(ty),
+                    span: start.to(end),
+                },
+                None => Expr {
+                    kind: ExprKind::Error,
+                    span: start.to(lexer.next_span()),
+                },
+            }
+        }
+        Some(TokenKind::OpeningBrace) => {
+            lexer.consume_token();
+            match parse_group(lexer, start, TokenKind::ClosingBrace, |lexer| {
+                let num_errors = lexer.errors.len();
+                let mut stmts = parse_statements(lexer);
+                match stmts.pop() {
+                    Some(Stmt::Expr(value)) => Ok((stmts, value)),
+                    last => {
+                        // The last statement may have been dropped because of an earlier error.
+                        if lexer.errors.len() == num_errors {
+                            let span = match last {
+                                Some(Stmt::Let { span, .. } | Stmt::Type { span, .. }) => span,
+                                _ => start.to(lexer.next_span()),
+                            };
+                            lexer.errors.push(ParseError::MissingBlockValue(span));
+                        }
+                        let value = Expr {
+                            kind: ExprKind::Error,
+                            span: start,
+                        };
+                        Ok((stmts, value))
+                    }
+                }
+            }) {
+                Some(((stmts, value), end)) => Expr {
+                    kind: ExprKind::Block {
+                        stmts,
+                        value: Box::new(value),
+                    },
+                    span: start.to(end),
+                },
+                None => Expr {
+                    kind: ExprKind::Error,
+                    span: start.to(lexer.next_span()),
+                },
+            }
+        }
+        _ => return Ok(None),
     };
-    while let Some(Token::OpeningParenthesis) = lexer.next_token {
-        lexer.consume_token();
-        let mut args = Vec::new();
-        loop {
-            let arg = parse_binary_operation(lexer);
-            if let Some(Token::Comma) = lexer.next_token {
+    loop {
+        expr = match lexer.next_kind() {
+            Some(TokenKind::OpeningParenthesis) => {
+                let opening = lexer.next_span();
+                lexer.consume_token();
+                match parse_group(lexer, opening, TokenKind::ClosingParenthesis, |lexer| {
+                    Ok(parse_list(lexer, parse_binary_operation))
+                }) {
+                    Some((args, end)) => Expr {
+                        span: expr.span.to(end),
+                        kind: ExprKind::Call(Box::new(expr), args),
+                    },
+                    None => Expr {
+                        span: expr.span.to(lexer.next_span()),
+                        kind: ExprKind::Error,
+                    },
+                }
+            }
+            Some(TokenKind::Dot) => {
+                let dot = lexer.next_span();
+                lexer.consume_token();
+                let index_span = lexer.next_span();
+                let index = match lexer.next_kind() {
+                    Some(TokenKind::Integer(digits)) => digits
+                        .parse()
+                        .map_err(|_| ParseError::IntegerOutOfRange(index_span))?,
+                    _ => return Err(ParseError::MissingIndex(dot.to(index_span))),
+                };
                 lexer.consume_token();
-                args.push(arg.expect("empty argument"));
+                Expr {
+                    span: expr.span.to(index_span),
+                    kind: ExprKind::Proj(Box::new(expr), index),
+                }
+            }
+            _ => return Ok(Some(expr)),
+        };
+    }
+}
+
+/// Parses the arms of `match`, each `PATTERN => EXPR`, separated by `,` or newlines, up to the closing `}`.
+fn parse_arms(lexer: &mut Lexer) -> Result<Vec<Arm>, ParseError> {
+    let mut arms = Vec::new();
+    loop {
+        while let Some(TokenKind::Newline | TokenKind::Comma) = lexer.next_kind() {
+            lexer.consume_token();
+        }
+        let Some(pattern) = parse_pattern(lexer)? else {
+            return Ok(arms);
+        };
+        let arrow = lexer.next_span();
+        expect(lexer, TokenKind::FatArrow)?;
+        let body = parse_assign(lexer, false)?
+            .ok_or_else(|| ParseError::MissingBody(arrow.to(lexer.next_span())))?;
+        arms.push(Arm {
+            span: pattern.span.to(body.span),
+            pattern,
+            body,
+        });
+        match lexer.next_kind() {
+            None | Some(TokenKind::Newline | TokenKind::Comma | TokenKind::ClosingBrace) => {}
+            Some(kind) => return Err(ParseError::UnexpectedToken(kind.clone(), lexer.next_span())),
+        }
+    }
+}
+
+/// Parses `_`, a name, a literal, `CTOR(PATTERN, ...)` or `(PATTERN, PATTERN, ...)`.
+fn parse_pattern(lexer: &mut Lexer) -> Result<Option<Pattern>, ParseError> {
+    let start = lexer.next_span();
+    let kind = match lexer.next_token.as_mut().map(|token| &mut token.kind) {
+        Some(TokenKind::Identifier(name)) => {
+            let name = std::mem::take(name);
+            lexer.consume_token();
+            if name == "_" {
+                PatternKind::Wildcard
+            } else if let Some(TokenKind::OpeningParenthesis) = lexer.next_kind() {
+                let opening = lexer.next_span();
+                lexer.consume_token();
+                let Some((args, end)) =
+                    parse_group(lexer, opening, TokenKind::ClosingParenthesis, |lexer| {
+                        Ok(parse_list(lexer, parse_pattern))
+                    })
+                else {
+                    // The syntax error is already recorded, and the statement will be dropped.
+                    return Ok(Some(Pattern {
+                        kind: PatternKind::Wildcard,
+                        span: start,
+                    }));
+                };
+                return Ok(Some(Pattern {
+                    kind: PatternKind::Ctor(name, args),
+                    span: start.to(end),
+                }));
             } else {
-                args.extend(arg);
-                break;
+                PatternKind::Var(name)
             }
         }
-        assert!(matches!(lexer.next_token, Some(Token::ClosingParenthesis)));
+        Some(TokenKind::True) => {
+            lexer.consume_token();
+            PatternKind::Bool(true)
+        }
+        Some(TokenKind::False) => {
+            lexer.consume_token();
+            PatternKind::Bool(false)
+        }
+        Some(TokenKind::Integer(_) | TokenKind::Hyphen) => {
+            let sign = if let Some(TokenKind::Hyphen) = lexer.next_kind() {
+                lexer.consume_token();
+                "-"
+            } else {
+                ""
+            };
+            let span = start.to(lexer.next_span());
+            let Some(token @ TokenKind::Integer(_)) = lexer.next_kind() else {
+                return Err(ParseError::InvalidPattern(span));
+            };
+            let ExprKind::Int(value) = number(token, sign, span)? else {
+                unreachable!("an `Integer` token must make an integer")
+            };
+            lexer.consume_token();
+            return Ok(Some(Pattern {
+                kind: PatternKind::Int(value),
+                span,
+            }));
+        }
+        Some(TokenKind::OpeningParenthesis) => {
+            lexer.consume_token();
+            let Some((mut items, end)) =
+                parse_group(lexer, start, TokenKind::ClosingParenthesis, |lexer| {
+                    Ok(parse_list(lexer, parse_pattern))
+                })
+            else {
+                return Ok(Some(Pattern {
+                    kind: PatternKind::Wildcard,
+                    span: start,
+                }));
+            };
+            let kind = match items.len() {
+                0 => return Err(ParseError::EmptyParentheses(start.to(end))),
+                1 => items.pop().unwrap().kind,
+                _ => PatternKind::Tuple(items),
+            };
+            return Ok(Some(Pattern {
+                kind,
+                span: start.to(end),
+            }));
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(Pattern { kind, span: start }))
+}
+
+/// Converts an `Integer` or `Float` token, preceded by `sign`, to a literal.
+fn number(token: &TokenKind, sign: &str, span: Span) -> Result<ExprKind, ParseError> {
+    match token {
+        TokenKind::Float(text) => match format!("{sign}{text}").parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(ExprKind::Float(value)),
+            _ => Err(ParseError::FloatOutOfRange(span)),
+        },
+        TokenKind::Integer(text) => format!("{sign}{text}")
+            .parse()
+            .map(ExprKind::Int)
+            .map_err(|_| ParseError::IntegerOutOfRange(span)),
+        _ => unreachable!("a number must be an `Integer` or `Float` token"),
+    }
+}
+
+/// Parses an operand of a keyword expression that starts at `start`.
+fn parse_operand(lexer: &mut Lexer, start: Span) -> Result<Expr, ParseError> {
+    parse_binary_operation(lexer)?
+        .ok_or_else(|| ParseError::MissingExpression(start.to(lexer.next_span())))
+}
+
+/// Consumes the next token, which must be `expected`.
+fn expect(lexer: &mut Lexer, expected: TokenKind) -> Result<(), ParseError> {
+    match lexer.next_kind() {
+        Some(kind) if std::mem::discriminant(kind) == std::mem::discriminant(&expected) => {
+            lexer.consume_token();
+            Ok(())
+        }
+        _ => Err(ParseError::Expected(expected, lexer.next_span())),
+    }
+}
+
+/// Parses an expression optionally followed by `: TYPE`, which is a lambda parameter if `=>` follows the parentheses.
+fn parse_annotated(lexer: &mut Lexer) -> Result<Option<(Expr, Option<Ty>)>, ParseError> {
+    let Some(expr) = parse_binary_operation(lexer)? else {
+        return Ok(None);
+    };
+    if let Some(TokenKind::Colon) = lexer.next_kind() {
+        let colon = lexer.next_span();
         lexer.consume_token();
-        expr = Expr::Call(Box::new(expr), args);
+        let ty = parse_ty(lexer)?
+            .ok_or_else(|| ParseError::MissingType(colon.to(lexer.next_span())))?;
+        Ok(Some((expr, Some(ty))))
+    } else {
+        Ok(Some((expr, None)))
     }
-    Some(expr)
 }
 
-fn parse_ty(lexer: &mut Lexer) -> Option<Ty> {
-    match lexer.next_token {
-        Some(Token::Identifier(ref name)) => {
+fn parse_ty(lexer: &mut Lexer) -> Result<Option<Ty>, ParseError> {
+    let Some(start) = lexer.next_token.as_ref().map(|token| token.span) else {
+        return Ok(None);
+    };
+    match lexer.next_kind() {
+        Some(TokenKind::Identifier(name)) => {
             let ret = match &name[..] {
-                "Int" => Some(Ty::Int),
-                _ => None,
+                "Int" => Ty {
+                    kind: TyKind::Int,
+                    span: start,
+                },
+                "Float" => Ty {
+                    kind: TyKind::Float,
+                    span: start,
+                },
+                "Bool" => Ty {
+                    kind: TyKind::Bool,
+                    span: start,
+                },
+                "Str" => Ty {
+                    kind: TyKind::Str,
+                    span: start,
+                },
+                _ => Ty {
+                    kind: TyKind::Named(name.clone()),
+                    span: start,
+                },
             };
             lexer.consume_token();
-            ret
+            Ok(Some(ret))
         }
-        Some(Token::OpeningParenthesis) => {
+        Some(TokenKind::OpeningParenthesis) => {
             lexer.consume_token();
-            let mut args = Vec::new();
-            loop {
-                let arg = parse_ty(lexer);
-                if let Some(Token::Comma) = lexer.next_token {
-                    lexer.consume_token();
-                    args.push(arg.expect("empty argument"));
-                } else {
-                    args.extend(arg);
-                    break;
+            let Some((args, end)) =
+                parse_group(lexer, start, TokenKind::ClosingParenthesis, |lexer| {
+                    Ok(parse_list(lexer, parse_ty))
+                })
+            else {
+                let ret = parse_ty(lexer)?;
+                return Ok(Some(Ty {
+                    kind: TyKind::Error,
+                    span: ret.map_or(start, |ret| start.to(ret.span)),
+                }));
+            };
+            let Some(ret) = parse_ty(lexer)? else {
+                // Without a return type, two or more types make a tuple type.
+                if args.len() < 2 {
+                    return Err(ParseError::MissingReturnType(end.to(lexer.next_span())));
                 }
+                return Ok(Some(Ty {
+                    kind: TyKind::Tuple(args),
+                    span: start.to(end),
+                }));
+            };
+            Ok(Some(Ty {
+                span: start.to(ret.span),
+                kind: TyKind::Func {
+                    args,
+                    ret: Box::new(ret),
+                },
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+impl ParseError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ParseError::UnexpectedCharacter(ch, span) => {
+                Diagnostic::error(format!("unexpected character {ch:?}"), *span)
+            }
+            ParseError::UnexpectedToken(token, span) => {
+                Diagnostic::error(format!("unexpected token `{token}`"), *span)
+            }
+            ParseError::IntegerOutOfRange(span) => {
+                Diagnostic::error("integer literal out of range", *span)
+                    .with_note(format!("integers range from {} to {}", i32::MIN, i32::MAX))
+            }
+            ParseError::Unclosed {
+                opening,
+                expected,
+                found,
+            } => Diagnostic::error(format!("expected `{expected}`"), *found)
+                .with_label(format!("expected `{expected}` here"))
+                .with_note(format!("the group was opened at {}", opening.start)),
+            ParseError::FloatOutOfRange(span) => {
+                Diagnostic::error("float literal out of range", *span)
+                    .with_note(format!("floats must be at most {:e} in magnitude", f64::MAX))
+            }
+            ParseError::UnterminatedString(span) => {
+                Diagnostic::error("unterminated string literal", *span)
+                    .with_note("a string must end on the line where it starts; write a newline as `\\n`")
+            }
+            ParseError::InvalidEscape(span) => Diagnostic::error("invalid escape sequence", *span)
+                .with_note(r#"the escape sequences are `\n`, `\r`, `\t`, `\0`, `\\`, `\"` and `\u{...}` with a hexadecimal code point"#),
+            ParseError::EmptyParentheses(span) => Diagnostic::error("empty parentheses", *span),
+            ParseError::EmptyBrackets(span) => Diagnostic::error("empty brackets", *span)
+                .with_note("brackets must contain a type, as in `[Int]`"),
+            ParseError::EmptyArgument(span) => Diagnostic::error("empty argument", *span),
+            ParseError::MissingOperand(span) => Diagnostic::error("missing operand", *span),
+            ParseError::MissingRightHandSide(span) => {
+                Diagnostic::error("missing right hand side", *span)
+            }
+            ParseError::MissingReturnType(span) => Diagnostic::error("missing return type", *span),
+            ParseError::MissingType(span) => Diagnostic::error("missing type", *span),
+            ParseError::MissingBody(span) => Diagnostic::error("missing expression after `=>`", *span),
+            ParseError::InvalidParameter(span) => Diagnostic::error("invalid parameter", *span)
+                .with_label("expected a name, optionally followed by `: TYPE`"),
+            ParseError::UnexpectedAnnotation(span) => {
+                Diagnostic::error("unexpected type annotation", *span)
+                    .with_note("types can only be annotated on lambda parameters, as in `(a: Int) => a`")
+            }
+            ParseError::MissingIndex(span) => Diagnostic::error("expected a tuple index", *span)
+                .with_note("components of a tuple are numbered from 0, as in `t.0`"),
+            ParseError::InvalidPattern(span) => Diagnostic::error("invalid pattern", *span)
+                .with_note("a pattern is `_`, a name, a literal, a constructor or a tuple of patterns"),
+            ParseError::MissingExpression(span) => {
+                Diagnostic::error("expected an expression", *span)
+            }
+            ParseError::Expected(expected, span) => {
+                Diagnostic::error(format!("expected `{expected}`"), *span)
+            }
+            ParseError::MissingName(span) => Diagnostic::error("expected a name", *span),
+            ParseError::MissingInitializer(span) => {
+                Diagnostic::error("missing initializer", *span)
+                    .with_note("a variable must be given a value, as in `let a = 1`")
+            }
+            ParseError::MissingBlockValue(span) => {
+                Diagnostic::error("block does not end with an expression", *span)
+                    .with_note("the last statement of a block is its value, as in `{ let a = 1; a + 2 }`")
             }
-            assert!(matches!(lexer.next_token, Some(Token::ClosingParenthesis)));
-            lexer.consume_token();
-            let ret = parse_ty(lexer).expect("empty return type");
-            Some(Ty::Func {
-                args,
-                ret: Box::new(ret),
-            })
         }
-        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The statements of `input` without syntax errors as printed,
+    /// and the line number and message of each syntax error.
+    fn parse_all(input: &str) -> (Vec<String>, Vec<(usize, String)>) {
+        let (statements, errors) = parse(input, 0);
+        let errors = errors.iter().map(|err| {
+            let diagnostic = err.to_diagnostic();
+            (diagnostic.span.start.line + 1, diagnostic.message)
+        });
+        (
+            statements.iter().map(ToString::to_string).collect(),
+            errors.collect(),
+        )
+    }
+
+    #[test]
+    fn statements_after_an_error_are_parsed() {
+        let (statements, errors) = parse_all("a = (1 2)\nb = 2; c = ); d = 4\ne = 5");
+        assert_eq!(statements, ["(b = 2)", "(d = 4)", "(e = 5)"]);
+        assert_eq!(
+            errors,
+            [
+                (1, "expected `)`".to_string()),
+                (2, "missing right hand side".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_first_error_of_a_statement_is_reported() {
+        let (statements, errors) = parse_all("a = (1 +, 2 *, 3)\nb = f(, 1)\nc = 3");
+        assert_eq!(statements, ["(c = 3)"]);
+        assert_eq!(
+            errors,
+            [
+                (1, "missing operand".to_string()),
+                (2, "empty argument".to_string())
+            ]
+        );
+        let (statements, errors) = parse_all("x = 1 $ 2\ny = \"abc\nz = 3");
+        assert_eq!(statements, ["(z = 3)"]);
+        assert_eq!(
+            errors,
+            [
+                (1, "unexpected character '$'".to_string()),
+                (2, "unterminated string literal".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn unbalanced_groups() {
+        // A newline inside an open group does not end the statement.
+        let (statements, errors) = parse_all("a = [Int\nb = 6");
+        assert!(statements.is_empty());
+        assert_eq!(errors, [(2, "expected `]`".to_string())]);
+        let (statements, errors) = parse_all("a = 1 }\nb = 2");
+        assert_eq!(statements, ["(a = 1)", "(b = 2)"]);
+        assert_eq!(errors, [(1, "unexpected token `}`".to_string())]);
+    }
+
+    #[test]
+    fn integer_literals() {
+        let (statements, errors) = parse_all("a = -2147483648\nb = 2147483648\nc = -2147483649");
+        assert_eq!(statements, ["(a = -2147483648)"]);
+        assert_eq!(
+            errors,
+            [
+                (2, "integer literal out of range".to_string()),
+                (3, "integer literal out of range".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_more_input() {
+        // An open group, or an operator that needs a right-hand side.
+        for input in ["f(1,", "a = [Int", "g = {\n  x = 1", "1 +", "x =", "b = 1 &&\n", "(x: Int) =>"] {
+            assert!(super::needs_more_input(input), "`{input}` must continue");
+        }
+        for input in ["1 + 2", "f(1)", "g = { 1 }", "x = 1\n", "a = 1 }", "s = \"(", "1 # +"] {
+            assert!(!super::needs_more_input(input), "`{input}` must not continue");
+        }
     }
 }
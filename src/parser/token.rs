@@ -1,41 +1,109 @@
-use std::str::CharIndices;
+use super::ParseError;
+use crate::span::{Pos, Span};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::CharIndices,
+};
 
 #[derive(Debug)]
-pub enum Token {
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum TokenKind {
     Integer(String),
+    Float(String),
+    /// A string literal, with its escape sequences already replaced.
+    Str(String),
     Identifier(String),
     Equal,
+    DoubleEqual,
+    ExclamationEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    DoubleAmpersand,
+    DoubleVerticalLine,
+    VerticalLine,
+    Exclamation,
+    FatArrow,
+    Colon,
+    Dot,
     Plus,
     Hyphen,
     Asterisk,
     Slash,
     Percent,
     Comma,
+    Semicolon,
+    Newline,
     OpeningParenthesis,
     ClosingParenthesis,
     OpeningBracket,
     ClosingBracket,
+    OpeningBrace,
+    ClosingBrace,
+    Let,
+    Type,
+    Match,
+    If,
+    Then,
+    Else,
+    True,
+    False,
 }
 
 pub struct Lexer<'id> {
     input: &'id str,
     iter: CharIndices<'id>,
     next_index_char: Option<(usize, char)>,
+    next_pos: Pos,
+    /// The end of the last token.
+    prev_end: Pos,
+    /// For each open group, whether newlines directly inside it are skipped.
+    /// They are inside `(` and `[`, but separate statements inside `{`.
+    groups: Vec<bool>,
+    /// Whether the last token cannot end a statement, so that the next newline is skipped.
+    continues_line: bool,
+    /// Whether the last token is `.`.
+    after_dot: bool,
     pub next_token: Option<Token>,
+    pub errors: Vec<ParseError>,
+    /// The length of `errors` before `next_token` was read, so that an error in a token counts against the statement it starts.
+    pub errors_before_next: usize,
 }
 
 impl<'id> Lexer<'id> {
-    pub fn new(input: &'id str) -> Lexer<'id> {
+    /// `line` is the line number of `input` within the whole source.
+    pub fn new(input: &'id str, line: usize) -> Lexer<'id> {
         let mut ret = Lexer {
             input,
             iter: input.char_indices(),
             next_index_char: None,
+            next_pos: Pos { line, column: 0 },
+            prev_end: Pos { line, column: 0 },
+            groups: Vec::new(),
+            continues_line: false,
+            after_dot: false,
             next_token: None,
+            errors: Vec::new(),
+            errors_before_next: 0,
         };
-        ret.consume_char();
+        ret.next_index_char = ret.iter.next();
         ret.consume_token();
         ret
     }
+    /// Whether `input` ends inside an open group or after an operator, so that the statement continues on the next line.
+    pub fn needs_more_input(input: &str) -> bool {
+        let mut lexer = Lexer::new(input, 0);
+        while lexer.next_token.is_some() {
+            lexer.consume_token();
+        }
+        !lexer.groups.is_empty() || lexer.continues_line
+    }
     fn pos(&self) -> usize {
         match self.next_index_char {
             Some((index, _)) => index,
@@ -43,32 +111,97 @@ impl<'id> Lexer<'id> {
         }
     }
     fn consume_char(&mut self) {
+        if let Some((_, ch)) = self.next_index_char {
+            if ch == '\n' {
+                self.next_pos.line += 1;
+                self.next_pos.column = 0;
+            } else {
+                self.next_pos.column += 1;
+            }
+        }
         self.next_index_char = self.iter.next();
     }
     fn next_char(&self) -> Option<char> {
         self.next_index_char.map(|(_, ch)| ch)
     }
+    pub fn next_kind(&self) -> Option<&TokenKind> {
+        self.next_token.as_ref().map(|token| &token.kind)
+    }
+    /// The span of the next token, or the empty span just after the last token at the end of the input.
+    pub fn next_span(&self) -> Span {
+        match self.next_token {
+            Some(ref token) => token.span,
+            None => Span::new(self.prev_end, self.prev_end),
+        }
+    }
+    /// Reads the next token. Unexpected characters are recorded in `errors` and skipped.
     pub fn consume_token(&mut self) {
-        while self.next_char().is_some_and(|ch| ch.is_ascii_whitespace()) {
-            self.consume_char();
+        self.errors_before_next = self.errors.len();
+        self.next_token = loop {
+            match self.lex_token() {
+                Ok(token) => break token,
+                Err(err) => self.errors.push(err),
+            }
+        };
+    }
+    fn lex_token(&mut self) -> Result<Option<Token>, ParseError> {
+        loop {
+            match self.next_char() {
+                Some('\n') if self.groups.last() != Some(&true) && !self.continues_line => break,
+                Some(ch) if ch.is_ascii_whitespace() => self.consume_char(),
+                Some('#') => {
+                    while self.next_char().is_some_and(|ch| ch != '\n') {
+                        self.consume_char();
+                    }
+                }
+                _ => break,
+            }
         }
         let start = self.pos();
-        self.next_token = match self.next_char() {
+        let start_pos = self.next_pos;
+        let kind = match self.next_char() {
             None => None,
             Some(first_token) => {
                 self.consume_char();
                 match first_token {
                     '0'..='9' => {
                         let mut value = first_token.to_string();
-                        while let Some(ch) = self.next_char() {
-                            match ch {
-                                '0'..='9' => value.push(ch),
-                                '_' => {}
-                                _ => break,
-                            }
+                        self.lex_digits(&mut value);
+                        let mut is_float = false;
+                        // After `.`, a number is a tuple index, so that `t.0.1` is not read as `t` `.` `0.1`.
+                        if !self.after_dot
+                            && self.next_char() == Some('.')
+                            && self.char_after_next().is_some_and(|ch| ch.is_ascii_digit())
+                        {
                             self.consume_char();
+                            value.push('.');
+                            self.lex_digits(&mut value);
+                            is_float = true;
+                        }
+                        if let (false, Some('e' | 'E')) = (self.after_dot, self.next_char()) {
+                            let sign = match self.char_after_next() {
+                                Some(sign @ ('+' | '-')) => Some(sign),
+                                _ => None,
+                            };
+                            let exponent_start = self.input[self.pos()..]
+                                .chars()
+                                .nth(1 + usize::from(sign.is_some()));
+                            if exponent_start.is_some_and(|ch| ch.is_ascii_digit()) {
+                                self.consume_char();
+                                value.push('e');
+                                if let Some(sign) = sign {
+                                    self.consume_char();
+                                    value.push(sign);
+                                }
+                                self.lex_digits(&mut value);
+                                is_float = true;
+                            }
+                        }
+                        if is_float {
+                            Some(TokenKind::Float(value))
+                        } else {
+                            Some(TokenKind::Integer(value))
                         }
-                        Some(Token::Integer(value))
                     }
                     'a'..='z' | 'A'..='Z' | '_' => {
                         while let Some('a'..='z' | 'A'..='Z' | '0'..='9' | '_') = self.next_char() {
@@ -76,29 +209,320 @@ impl<'id> Lexer<'id> {
                         }
                         let end = self.pos();
                         match &self.input[start..end] {
-                            s => Some(Token::Identifier(s.to_string())),
+                            "let" => Some(TokenKind::Let),
+                            "type" => Some(TokenKind::Type),
+                            "match" => Some(TokenKind::Match),
+                            "if" => Some(TokenKind::If),
+                            "then" => Some(TokenKind::Then),
+                            "else" => Some(TokenKind::Else),
+                            "true" => Some(TokenKind::True),
+                            "false" => Some(TokenKind::False),
+                            name => Some(TokenKind::Identifier(name.to_string())),
                         }
                     }
-                    '#' => {
-                        while self.next_char().is_some() {
+                    '=' => match self.next_char() {
+                        Some('>') => {
+                            self.consume_char();
+                            Some(TokenKind::FatArrow)
+                        }
+                        Some('=') => {
                             self.consume_char();
+                            Some(TokenKind::DoubleEqual)
                         }
-                        None
+                        _ => Some(TokenKind::Equal),
+                    },
+                    '!' => Some(self.lex_pair('=', TokenKind::ExclamationEqual, TokenKind::Exclamation)),
+                    '<' => Some(self.lex_pair('=', TokenKind::LessEqual, TokenKind::Less)),
+                    '>' => Some(self.lex_pair('=', TokenKind::GreaterEqual, TokenKind::Greater)),
+                    '&' if self.next_char() == Some('&') => {
+                        self.consume_char();
+                        Some(TokenKind::DoubleAmpersand)
+                    }
+                    '|' => Some(self.lex_pair(
+                        '|',
+                        TokenKind::DoubleVerticalLine,
+                        TokenKind::VerticalLine,
+                    )),
+                    '"' => Some(self.lex_string(start_pos)?),
+                    ':' => Some(TokenKind::Colon),
+                    '.' => Some(TokenKind::Dot),
+                    '+' => Some(TokenKind::Plus),
+                    '-' => Some(TokenKind::Hyphen),
+                    '*' => Some(TokenKind::Asterisk),
+                    '%' => Some(TokenKind::Percent),
+                    '/' => Some(TokenKind::Slash),
+                    ',' => Some(TokenKind::Comma),
+                    ';' => Some(TokenKind::Semicolon),
+                    '\n' => Some(TokenKind::Newline),
+                    '(' => Some(TokenKind::OpeningParenthesis),
+                    ')' => Some(TokenKind::ClosingParenthesis),
+                    '[' => Some(TokenKind::OpeningBracket),
+                    ']' => Some(TokenKind::ClosingBracket),
+                    '{' => Some(TokenKind::OpeningBrace),
+                    '}' => Some(TokenKind::ClosingBrace),
+                    _ => {
+                        return Err(ParseError::UnexpectedCharacter(
+                            first_token,
+                            Span::new(start_pos, self.next_pos),
+                        ))
                     }
-                    '=' => Some(Token::Equal),
-                    '+' => Some(Token::Plus),
-                    '-' => Some(Token::Hyphen),
-                    '*' => Some(Token::Asterisk),
-                    '%' => Some(Token::Percent),
-                    '/' => Some(Token::Slash),
-                    ',' => Some(Token::Comma),
-                    '(' => Some(Token::OpeningParenthesis),
-                    ')' => Some(Token::ClosingParenthesis),
-                    '[' => Some(Token::OpeningBracket),
-                    ']' => Some(Token::ClosingBracket),
-                    _ => panic!("unexpected character {first_token:?}"),
                 }
             }
+        };
+        match kind {
+            Some(TokenKind::OpeningParenthesis | TokenKind::OpeningBracket) => self.groups.push(true),
+            Some(TokenKind::OpeningBrace) => self.groups.push(false),
+            Some(
+                TokenKind::ClosingParenthesis
+                | TokenKind::ClosingBracket
+                | TokenKind::ClosingBrace,
+            ) => {
+                self.groups.pop();
+            }
+            _ => {}
+        }
+        if let Some(ref kind) = kind {
+            self.prev_end = self.next_pos;
+            self.after_dot = matches!(kind, TokenKind::Dot);
+            self.continues_line = matches!(
+                kind,
+                TokenKind::Equal
+                    | TokenKind::DoubleEqual
+                    | TokenKind::ExclamationEqual
+                    | TokenKind::Less
+                    | TokenKind::LessEqual
+                    | TokenKind::Greater
+                    | TokenKind::GreaterEqual
+                    | TokenKind::DoubleAmpersand
+                    | TokenKind::DoubleVerticalLine
+                    | TokenKind::VerticalLine
+                    | TokenKind::Exclamation
+                    | TokenKind::If
+                    | TokenKind::Then
+                    | TokenKind::Else
+                    | TokenKind::FatArrow
+                    | TokenKind::Plus
+                    | TokenKind::Hyphen
+                    | TokenKind::Asterisk
+                    | TokenKind::Slash
+                    | TokenKind::Percent
+                    | TokenKind::Comma
+            );
+        }
+        Ok(kind.map(|kind| Token {
+            kind,
+            span: Span::new(start_pos, self.next_pos),
+        }))
+    }
+    /// Appends the digits that come next to `value`, skipping `_` between them.
+    fn lex_digits(&mut self, value: &mut String) {
+        while let Some(ch) = self.next_char() {
+            match ch {
+                '0'..='9' => value.push(ch),
+                '_' => {}
+                _ => break,
+            }
+            self.consume_char();
         }
     }
+    /// Reads the rest of a string literal that started with `"` at `start`.
+    /// An invalid escape sequence is recorded in `errors` and skipped, so that the rest of the literal is still read.
+    fn lex_string(&mut self, start: Pos) -> Result<TokenKind, ParseError> {
+        let mut value = String::new();
+        loop {
+            let escape_start = self.next_pos;
+            match self.next_char() {
+                None | Some('\n') => {
+                    // The literal stands for an operand, so the newline after it ends the statement.
+                    self.continues_line = false;
+                    return Err(ParseError::UnterminatedString(Span::new(start, self.next_pos)));
+                }
+                Some('"') => {
+                    self.consume_char();
+                    return Ok(TokenKind::Str(value));
+                }
+                Some('\\') => {
+                    self.consume_char();
+                    match self.lex_escape() {
+                        Some(ch) => value.push(ch),
+                        None => self
+                            .errors
+                            .push(ParseError::InvalidEscape(Span::new(escape_start, self.next_pos))),
+                    }
+                }
+                Some(ch) => {
+                    self.consume_char();
+                    value.push(ch);
+                }
+            }
+        }
+    }
+    /// Reads an escape sequence after `\`: one of `\n`, `\r`, `\t`, `\0`, `\\`, `\"`,
+    /// or `\u{...}` with the code point of any Unicode character in hexadecimal.
+    fn lex_escape(&mut self) -> Option<char> {
+        let ch = match self.next_char()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            'u' => {
+                self.consume_char();
+                if self.next_char()? != '{' {
+                    return None;
+                }
+                self.consume_char();
+                let mut code = String::new();
+                while let Some(ch) = self.next_char().filter(char::is_ascii_hexdigit) {
+                    code.push(ch);
+                    self.consume_char();
+                }
+                if self.next_char()? != '}' {
+                    return None;
+                }
+                self.consume_char();
+                if code.is_empty() || code.len() > 6 {
+                    return None;
+                }
+                return char::from_u32(u32::from_str_radix(&code, 16).ok()?);
+            }
+            '\n' => return None,
+            _ => {
+                self.consume_char();
+                return None;
+            }
+        };
+        self.consume_char();
+        Some(ch)
+    }
+    /// The character after `next_char`.
+    fn char_after_next(&self) -> Option<char> {
+        self.input[self.pos()..].chars().nth(1)
+    }
+    /// Reads `second` if it comes next, and returns `pair` if it did or `single` otherwise.
+    fn lex_pair(&mut self, second: char, pair: TokenKind, single: TokenKind) -> TokenKind {
+        if self.next_char() == Some(second) {
+            self.consume_char();
+            pair
+        } else {
+            single
+        }
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?}", self.span, self.kind)
+    }
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Integer(value) | TokenKind::Float(value) => write!(f, "{value}"),
+            TokenKind::Str(value) => write!(f, "{value:?}"),
+            TokenKind::Identifier(name) => write!(f, "{name}"),
+            TokenKind::Equal => write!(f, "="),
+            TokenKind::DoubleEqual => write!(f, "=="),
+            TokenKind::ExclamationEqual => write!(f, "!="),
+            TokenKind::Less => write!(f, "<"),
+            TokenKind::LessEqual => write!(f, "<="),
+            TokenKind::Greater => write!(f, ">"),
+            TokenKind::GreaterEqual => write!(f, ">="),
+            TokenKind::DoubleAmpersand => write!(f, "&&"),
+            TokenKind::DoubleVerticalLine => write!(f, "||"),
+            TokenKind::VerticalLine => write!(f, "|"),
+            TokenKind::Exclamation => write!(f, "!"),
+            TokenKind::FatArrow => write!(f, "=>"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::Plus => write!(f, "+"),
+            TokenKind::Hyphen => write!(f, "-"),
+            TokenKind::Asterisk => write!(f, "*"),
+            TokenKind::Slash => write!(f, "/"),
+            TokenKind::Percent => write!(f, "%"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::Newline => write!(f, "\\n"),
+            TokenKind::OpeningParenthesis => write!(f, "("),
+            TokenKind::ClosingParenthesis => write!(f, ")"),
+            TokenKind::OpeningBracket => write!(f, "["),
+            TokenKind::ClosingBracket => write!(f, "]"),
+            TokenKind::OpeningBrace => write!(f, "{{"),
+            TokenKind::ClosingBrace => write!(f, "}}"),
+            TokenKind::Let => write!(f, "let"),
+            TokenKind::Type => write!(f, "type"),
+            TokenKind::Match => write!(f, "match"),
+            TokenKind::If => write!(f, "if"),
+            TokenKind::Then => write!(f, "then"),
+            TokenKind::Else => write!(f, "else"),
+            TokenKind::True => write!(f, "true"),
+            TokenKind::False => write!(f, "false"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The values of the string literals in `input`, the number of other tokens,
+    /// and the message and columns of each error.
+    fn strings(input: &str) -> (Vec<String>, usize, Vec<(String, usize, usize)>) {
+        let mut lexer = Lexer::new(input, 0);
+        let mut strings = Vec::new();
+        let mut others = 0;
+        while let Some(token) = lexer.next_token.take() {
+            match token.kind {
+                TokenKind::Str(value) => strings.push(value),
+                _ => others += 1,
+            }
+            lexer.consume_token();
+        }
+        let errors = lexer.errors.iter().map(|err| {
+            let diagnostic = err.to_diagnostic();
+            let span = diagnostic.span;
+            (diagnostic.message, span.start.column, span.end.column)
+        });
+        (strings, others, errors.collect())
+    }
+
+    #[test]
+    fn escapes() {
+        let (values, _, errors) = strings(r#""a\tb\\\"c\n" "\u{48}\u{1F600}é\0""#);
+        assert_eq!(values, ["a\tb\\\"c\n", "H\u{1F600}é\0"]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn invalid_escapes_are_skipped() {
+        let invalid = |start, end| ("invalid escape sequence".to_string(), start, end);
+        let (values, _, errors) = strings(r#""a\qb" "\u{110000}" "\u{}" "\u48""#);
+        assert_eq!(values, ["ab", "", "", "48"]);
+        assert_eq!(
+            errors,
+            [
+                invalid(2, 4),
+                invalid(8, 18),
+                invalid(21, 25),
+                invalid(28, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        let unterminated = |start, end| ("unterminated string literal".to_string(), start, end);
+        // The literal ends at the end of the line, and the next line is read as usual.
+        let (values, others, errors) = strings("\"abc\ny = 1");
+        assert!(values.is_empty());
+        assert_eq!(others, 4);
+        assert_eq!(errors, [unterminated(0, 4)]);
+        // A `\` at the end of the input escapes nothing.
+        let (values, _, errors) = strings("\"abc\\");
+        assert!(values.is_empty());
+        let invalid = ("invalid escape sequence".to_string(), 4, 5);
+        assert_eq!(errors, [invalid, unterminated(0, 5)]);
+    }
 }
@@ -0,0 +1,86 @@
+use crate::{
+    parser,
+    session::{Mode, Session},
+};
+use std::io::{BufRead, IsTerminal, Write};
+
+const HELP: &str = "\
+:type EXPR  Print the type of EXPR without evaluating it
+:env        Print every variable with its type and value
+:reset      Forget every variable
+:load FILE  Evaluate every line of FILE
+:help       Print this message";
+
+/// Reads statements from standard input and evaluates each as soon as it is entered.
+/// Returns whether there was no error.
+pub fn run() -> bool {
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut session = Session::new("<repl>", Mode::Repl, &[]);
+    let mut lines = stdin.lock().lines();
+    // The lines of a statement that continues on the next line.
+    let mut pending = String::new();
+    loop {
+        if interactive {
+            print!("{}", if pending.is_empty() { "> " } else { ". " });
+            std::io::stdout().flush().expect("failed to write to stdout");
+        }
+        match lines.next() {
+            Some(Ok(line)) => {
+                if pending.is_empty() {
+                    if let Some(command) = line.trim_start().strip_prefix(':') {
+                        meta_command(&mut session, &line, command);
+                        continue;
+                    }
+                } else {
+                    pending.push('\n');
+                }
+                pending.push_str(&line);
+                // An empty line ends the statement even if it is incomplete.
+                if line.trim().is_empty() || !parser::needs_more_input(&pending) {
+                    session.process(&pending);
+                    pending.clear();
+                }
+            }
+            Some(Err(err)) => {
+                eprintln!("error: cannot read from stdin: {err}");
+                return false;
+            }
+            None => break,
+        }
+    }
+    if !pending.is_empty() {
+        session.process(&pending);
+    }
+    if interactive {
+        println!();
+    }
+    session.finish()
+}
+
+/// Runs `command`, the part of `line` after `:`.
+/// The line counts in the source of the session, so that the lines after it keep their numbers in diagnostics.
+fn meta_command(session: &mut Session, line: &str, command: &str) {
+    let (name, arg) = command
+        .split_once(char::is_whitespace)
+        .map_or((command, ""), |(name, arg)| (name, arg.trim()));
+    if matches!(name, "type" | "t") && !arg.is_empty() {
+        // The expression ends the line, and keeps its columns in diagnostics.
+        return session.print_type(line, line.trim_end().len() - arg.len());
+    }
+    session.skip_line(line);
+    match (name, arg) {
+        ("type" | "t", "") | ("load" | "l", "") => eprintln!("error: `:{name}` needs an argument"),
+        ("env" | "e", "") => session.print_env(),
+        ("reset" | "r", "") => session.reset(),
+        ("load" | "l", path) => match std::fs::read_to_string(path) {
+            Ok(source) => session.process_file(path, &source),
+            Err(err) => eprintln!("error: cannot read `{path}`: {err}"),
+        },
+        ("help" | "h" | "?", "") => println!("{HELP}"),
+        ("env" | "e" | "reset" | "r" | "help" | "h" | "?", _) => {
+            eprintln!("error: `:{name}` takes no argument")
+        }
+        _ => eprintln!("error: unknown command `:{name}`; try `:help`"),
+    }
+}
@@ -0,0 +1,571 @@
+use crate::{
+    ast,
+    cli::Stage,
+    context::Context,
+    diagnostic::Diagnostic,
+    ir, parser,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Evaluate and print each statement with its IR, type and value.
+    Run,
+    /// Only type-check.
+    Check,
+    /// Evaluate and print each statement's value and type.
+    Repl,
+}
+
+/// A statement that passed the checks.
+struct Checked {
+    expr: ir::Expr,
+    ty: ir::Ty,
+    /// The derivation of the type, if `Stage::DerivationTex` is emitted.
+    proof: Option<ir::Proof>,
+    /// Whether each variable is assigned once the statement has run, to become `Session::vars_assigned` if it runs without error.
+    assigned: Vec<bool>,
+}
+
+/// The state kept between statements: the names and types of variables and their values.
+pub struct Session {
+    name: String,
+    mode: Mode,
+    emit: Vec<Stage>,
+    /// Where every type of the session is made, shared with `context`.
+    tys: ir::TyTable,
+    context: Context,
+    vars_ty: Vec<ir::Ty>,
+    vars: Vec<ir::Value>,
+    /// Whether each variable is assigned by the statements that passed the checks and ran without error so far.
+    vars_assigned: Vec<bool>,
+    /// Every line read so far, so that diagnostics can quote any of them.
+    source: String,
+    num_lines: usize,
+    num_errors: usize,
+}
+
+impl Session {
+    /// `name` is where the source comes from, as shown in diagnostics.
+    pub fn new(name: &str, mode: Mode, emit: &[Stage]) -> Session {
+        let tys = ir::TyTable::new();
+        Session {
+            name: name.to_string(),
+            mode,
+            emit: emit.to_vec(),
+            context: Context::new(&tys),
+            tys,
+            vars_ty: Vec::new(),
+            vars: Vec::new(),
+            vars_assigned: Vec::new(),
+            source: String::new(),
+            num_lines: 0,
+            num_errors: 0,
+        }
+    }
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.num_errors += 1;
+        eprint!("{}", diagnostic.render(&self.name, &self.source));
+    }
+    /// Appends `input` to the source, and returns the line number where it starts.
+    fn append_source(&mut self, input: &str) -> usize {
+        let line_number = self.num_lines;
+        self.num_lines += input.lines().count().max(1);
+        self.source.push_str(input);
+        self.source.push('\n');
+        line_number
+    }
+    /// Appends to the source a line that holds no statement, such as a command of the REPL,
+    /// so that the lines after it keep their numbers.
+    pub fn skip_line(&mut self, line: &str) {
+        self.append_source(line);
+    }
+    /// Appends `input` to the source and parses the part of it from byte `start`, reporting any syntax error.
+    fn parse(&mut self, input: &str, start: usize) -> Vec<ast::Stmt> {
+        let line_number = self.append_source(input);
+        // The part before `start` is blanked out, so that the columns of the tokens count from the start of `input`.
+        let blank = " ".repeat(input[..start].chars().count());
+        let input = &format!("{blank}{}", &input[start..]);
+        if self.emit.contains(&Stage::Tokens) {
+            for token in parser::tokenize(input, line_number) {
+                println!("{token}");
+            }
+        }
+        let (statements, errors) = parser::parse(input, line_number);
+        for err in errors {
+            self.report(err.to_diagnostic());
+        }
+        if self.emit.contains(&Stage::Ast) {
+            for statement in &statements {
+                println!("{statement}");
+            }
+        }
+        statements
+    }
+    /// Makes room for the variables that the last translation introduced.
+    fn add_new_variables(&mut self) {
+        while self.vars.len() < self.context.num_variables() {
+            self.vars_ty.push(self.tys.new_ty(ir::TyInner::Undetermined));
+            self.vars.push(ir::Value::Var(Rc::new(RefCell::new(None))));
+            self.vars_assigned.push(false);
+        }
+    }
+    /// Forgets the variables that a statement with an error introduced, which were never assigned.
+    fn forget_variables(&mut self, num_variables: usize) {
+        self.context.forget_variables(num_variables);
+        self.vars_ty.truncate(num_variables);
+        self.vars.truncate(num_variables);
+        self.vars_assigned.truncate(num_variables);
+    }
+    /// Parses `input`, and processes each statement in it that has no syntax error.
+    pub fn process(&mut self, input: &str) {
+        for statement in self.parse(input, 0) {
+            self.process_statement(statement);
+        }
+    }
+    /// Processes `input`, the contents of the file `name`, so that its diagnostics point into the file.
+    pub fn process_file(&mut self, name: &str, input: &str) {
+        let name = std::mem::replace(&mut self.name, name.to_string());
+        let source = std::mem::take(&mut self.source);
+        let num_lines = std::mem::replace(&mut self.num_lines, 0);
+        self.process(input);
+        self.name = name;
+        self.source = source;
+        self.num_lines = num_lines;
+    }
+    /// Translates one statement and checks that it reads only assigned variables and is well-typed,
+    /// giving it with its type and the variables assigned after it.
+    /// It gives `None` for a statement that declares a type, which has nothing to evaluate.
+    /// A statement that is rejected leaves the variables and their types as they were, and frees the types made for it.
+    fn check_statement(&mut self, statement: ast::Stmt) -> Result<Option<Checked>, Diagnostic> {
+        let num_variables = self.context.num_variables();
+        let checkpoint = self.tys.checkpoint();
+        let checked = self.translate_and_check(statement);
+        if checked.is_ok() {
+            self.tys.commit(checkpoint);
+        } else {
+            self.forget_variables(num_variables);
+            self.tys.rollback(checkpoint);
+        }
+        checked
+    }
+    /// Does the work of `check_statement`, leaving what a rejected statement introduced for it to undo.
+    fn translate_and_check(&mut self, statement: ast::Stmt) -> Result<Option<Checked>, Diagnostic> {
+        let expr = self.context.translate_stmt(statement);
+        self.add_new_variables();
+        let mut expr = match expr {
+            Ok(Some(expr)) => expr,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(err.to_diagnostic()),
+        };
+        if self.emit.contains(&Stage::Ir) {
+            println!("{expr}");
+        }
+        let mut assigned = self.vars_assigned.clone();
+        self.context
+            .check_assigned(&expr, &mut assigned)
+            .map_err(|err| err.to_diagnostic())?;
+        let checked = if self.emit.contains(&Stage::DerivationTex) {
+            expr.derive(&self.tys, &self.vars_ty).map(|(ty, proof)| (ty, Some(proof)))
+        } else {
+            expr.check(&self.tys, &self.vars_ty).map(|ty| (ty, None))
+        };
+        let (ty, proof) = checked.map_err(|err| err.to_diagnostic())?;
+        Ok(Some(Checked {
+            expr,
+            ty,
+            proof,
+            assigned,
+        }))
+    }
+    /// Translates, type-checks and (unless in `Mode::Check`) evaluates one statement.
+    fn process_statement(&mut self, statement: ast::Stmt) {
+        let Checked {
+            expr,
+            ty,
+            proof,
+            assigned,
+        } = match self.check_statement(statement) {
+            Ok(Some(checked)) => checked,
+            Ok(None) => return,
+            Err(diagnostic) => return self.report(diagnostic),
+        };
+        if self.emit.contains(&Stage::Types) {
+            println!("{expr}: {ty}");
+        }
+        if let Some(proof) = proof {
+            print!("{}", proof.to_tex());
+        }
+        if self.mode == Mode::Check {
+            self.vars_assigned = assigned;
+            return;
+        }
+        let value = match expr.eval(&self.vars, &None) {
+            Ok(value) => value,
+            Err(err) => return self.report(err.to_diagnostic(expr.span)),
+        };
+        self.vars_assigned = assigned;
+        if self.emit.contains(&Stage::Values) {
+            println!("{value}");
+        } else if self.emit.is_empty() {
+            match self.mode {
+                Mode::Repl => println!("{value}: {ty}"),
+                _ => println!("{expr}: {ty}\n  -> {value}"),
+            }
+        }
+    }
+    /// Prints the type of each statement in `line` from byte `start` without evaluating it,
+    /// where the part before it is the command of the REPL.
+    /// Neither the variables nor their types are changed, even by an assignment.
+    pub fn print_type(&mut self, line: &str, start: usize) {
+        let checkpoint = self.tys.checkpoint();
+        self.print_types(line, start);
+        self.tys.rollback(checkpoint);
+    }
+    /// Does the work of `print_type`, leaving the types it made and unified for it to undo.
+    fn print_types(&mut self, line: &str, start: usize) {
+        let mut context = self.context.clone();
+        let mut vars_ty = self.vars_ty.clone();
+        let mut assigned = self.vars_assigned.clone();
+        for statement in self.parse(line, start) {
+            let mut expr = match context.translate_stmt(statement) {
+                Ok(Some(expr)) => expr,
+                Ok(None) => continue,
+                Err(err) => return self.report(err.to_diagnostic()),
+            };
+            vars_ty.resize_with(context.num_variables(), || {
+                self.tys.new_ty(ir::TyInner::Undetermined)
+            });
+            assigned.resize(context.num_variables(), false);
+            if let Err(err) = context.check_assigned(&expr, &mut assigned) {
+                return self.report(err.to_diagnostic());
+            }
+            match expr.check(&self.tys, &vars_ty) {
+                Ok(ty) => println!("{ty}"),
+                Err(err) => return self.report(err.to_diagnostic()),
+            }
+        }
+    }
+    /// Prints every variable with its type and value.
+    pub fn print_env(&self) {
+        for (name, idx) in self.context.variables() {
+            let ir::Value::Var(ref var) = self.vars[idx] else {
+                unreachable!("a variable must hold a reference")
+            };
+            match *var.borrow() {
+                Some(ref value) => println!("{name}: {} = {value}", self.vars_ty[idx]),
+                None => println!("{name}: {} (uninitialized)", self.vars_ty[idx]),
+            }
+        }
+    }
+    /// Forgets every variable, and frees every type.
+    pub fn reset(&mut self) {
+        self.tys = ir::TyTable::new();
+        self.context = Context::new(&self.tys);
+        self.vars_ty.clear();
+        self.vars.clear();
+        self.vars_assigned.clear();
+    }
+    /// Returns whether there was no error.
+    pub fn finish(self) -> bool {
+        self.num_errors == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks and evaluates each statement of `input` in turn, giving `value: type` or the error message for each.
+    fn run(input: &str) -> Vec<String> {
+        run_in(&mut Session::new("test", Mode::Run, &[]), input)
+    }
+
+    /// Runs `input` as `run` does, in `session`.
+    fn run_in(session: &mut Session, input: &str) -> Vec<String> {
+        let (statements, errors) = parser::parse(input, 0);
+        assert!(errors.is_empty(), "`{input}` must parse");
+        let mut results = Vec::new();
+        for statement in statements {
+            let result = match session.check_statement(statement) {
+                Ok(Some(Checked {
+                    expr,
+                    ty,
+                    assigned,
+                    ..
+                })) => match expr.eval(&session.vars, &None) {
+                    Ok(value) => {
+                        session.vars_assigned = assigned;
+                        format!("{value}: {ty}")
+                    }
+                    Err(err) => err.to_diagnostic(expr.span).message,
+                },
+                Ok(None) => continue,
+                Err(diagnostic) => diagnostic.message,
+            };
+            results.push(result);
+        }
+        results
+    }
+
+    const THE_ANSWER: &str =
+        include_str!("../answer_to_the_ultimate_question_of_life_the_universe_and_everything");
+
+    #[test]
+    fn the_answer() {
+        assert_eq!(
+            run(THE_ANSWER),
+            [
+                "3: Int",
+                "Id: (Int)Int",
+                "2: Int",
+                "Mul(const 2, Id): (Int)Int",
+                "14: Int",
+                "Add(Mul(const 2, Id), Mul(const 2, Id)(Add(Id, const 1))): (Int)Int",
+                "42: Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn arguments_are_contravariant() {
+        let results = run(&format!(
+            "{THE_ANSWER}\
+             apply = (f: (Int) Int) => f(20)\n\
+             apply(z)\n\
+             apply(y)\n\
+             apply((x: Float) => 1)\n\
+             apply((x: Float) => x > 0.5)\n\
+             apply(z + 0.5)\n\
+             apply([Float] * 2.0)\n\
+             twice = (f: (Float) Float) => f(f(1.5))\n\
+             twice(z)\n\
+             twice([Float] + 1)\n\
+             twice((x: Float) => 2)\n\
+             halve = (f: ((Float) Float) Float) => f([Float] / 2.0)\n\
+             halve((g: (Float) Int) => g(1.0))\n\
+             halve((g: (Int) Float) => g(1))\n"
+        ));
+        assert_eq!(
+            results[7..],
+            [
+                "Lambda[(Int)Int](Deref[(Int)Int](&l0.0)(20)): ((Int)Int)Int",
+                "82: Int",
+                "40: Int",
+                "1: Int",
+                "mismatched types in argument 1",
+                "mismatched types in argument 1",
+                "mismatched types in argument 1",
+                "Lambda[(Float)Float](Deref[(Float)Float](&l0.0)(Deref[(Float)Float](&l0.0)(1.5))): ((Float)Float)Float",
+                "mismatched types in argument 1",
+                "3.5: Float",
+                "2.0: Float",
+                "Lambda[((Float)Float)Float](Deref[((Float)Float)Float](&l0.0)(Div(Id[Float], 2.0))): (((Float)Float)Float)Float",
+                "mismatched types in argument 1",
+                "0.5: Float",
+            ]
+        );
+    }
+
+    #[test]
+    fn constant_and_curried_arguments() {
+        let results = run(&format!(
+            "{THE_ANSWER}\
+             apply = (f: (Int) Int) => f(20)\n\
+             apply(x)\n\
+             apply(z(x))\n\
+             apply(y + [Int])\n\
+             curried = (f: (Int) (Bool) Int) => f(3)(true)\n\
+             curried(z)\n\
+             curried((n: Int) => (b: Bool) => n)\n\
+             y = 5\n\
+             y(1) + z(1)\n"
+        ));
+        assert_eq!(
+            results[7..],
+            [
+                "Lambda[(Int)Int](Deref[(Int)Int](&l0.0)(20)): ((Int)Int)Int",
+                "2: Int",
+                "10: Int",
+                "60: Int",
+                "Lambda[(Int)(Bool)Int](Deref[(Int)(Bool)Int](&l0.0)(3)(true)): ((Int)(Bool)Int)Int",
+                "14: Int",
+                "3: Int",
+                "const 5: (Int)Int",
+                "11: Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn recursion_through_an_undetermined_type() {
+        assert_eq!(
+            run("sum = (n: Int) => if n == 0 then 0 else n + sum(n - 1)\nsum(9)"),
+            [
+                "Lambda[Int](If[Int](Eq(Deref[Int](&l0.0), 0), 0, Add(Deref[Int](&l0.0), Deref[(Int)Int](&v0)(Sub(Deref[Int](&l0.0), 1))))): (Int)Int",
+                "45: Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn infinite_types() {
+        assert_eq!(
+            run("f = (x: Int) => f\ng = (x: Int) => g(x)(x)\nf = [Int]\nf(1)"),
+            [
+                "infinite type in argument 2",
+                "infinite type in argument 2",
+                "Id: (Int)Int",
+                "1: Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_arithmetic_is_checked() {
+        assert_eq!(
+            run("2147483647 + 1\n-2147483648 / -1\n-2147483648 - 1\n1 % 0\n1 / 0\n-2147483648"),
+            [
+                "integer overflow",
+                "integer overflow",
+                "integer overflow",
+                "division by zero",
+                "division by zero",
+                "-2147483648: Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn immediately_called_lambdas() {
+        assert_eq!(
+            run("((x: Int) => x + 1)(3)\n(if true then (x: Int) => x else (x: Int) => 0)(7)\n{ 2 }(1)"),
+            [
+                "4: Int",
+                "7: Int",
+                "`Int` is not a function",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_statements_leave_types_alone() {
+        assert_eq!(
+            run("id = (y) => y\nid(1) && true\nid(true)"),
+            [
+                "Lambda[?](Deref[?](&l0.0)): (?)?",
+                "mismatched types in argument 1",
+                "true: Bool",
+            ]
+        );
+    }
+
+    #[test]
+    fn variables_assigned_by_a_failed_statement_stay_unassigned() {
+        assert_eq!(
+            run("q = 1 / 0\nq\nq = 2\nq"),
+            [
+                "division by zero",
+                "variable `q` read before it is assigned",
+                "2: Int",
+                "2: Int",
+            ]
+        );
+    }
+
+    #[test]
+    fn lifted_conditionals_evaluate_only_the_operands_needed() {
+        let results = run("f = if [Int] == 0 then 0 else 10 / [Int]\n\
+             f(0)\n\
+             f(5)\n\
+             g = [Int] == 0 || 10 / [Int] > 1\n\
+             g(0)\n\
+             h = [Int] != 0 && 10 / [Int] > 1\n\
+             h(0)\n\
+             k = if (x: Int) => (y: Int) => y == 0 then 0 else (x: Int) => (y: Int) => 10 / y\n\
+             k(1)(0)\n\
+             k(1)(2)");
+        let values = [1, 2, 4, 6, 8, 9].map(|i| results[i].as_str());
+        assert_eq!(
+            values,
+            ["0: Int", "2: Int", "true: Bool", "false: Bool", "0: Int", "5: Int"]
+        );
+    }
+
+    #[test]
+    fn matching_on_a_function() {
+        let results = run("type S = A(Int) | B\n\
+             g = match [S] { A(n) => n, B => 0 }\n\
+             g(B)\n\
+             g(A(4))\n\
+             h = match [S] { A(1) => 1, _ => 0 }\n\
+             h(B)\n\
+             h(A(1))");
+        let values = [1, 2, 4, 5].map(|i| results[i].as_str());
+        assert_eq!(values, ["0: Int", "4: Int", "0: Int", "1: Int"]);
+    }
+
+    #[test]
+    fn destructuring_assignment() {
+        let results = run("a, b = 1, 2\n\
+             a, b = b, a\n\
+             (a, b)\n\
+             a, b = 1\n\
+             a, b = 1, 2, 3\n\
+             (a, b), c = 1, 2\n\
+             g = (x: Int) => (x, x + 1)\n\
+             e, f = g\n\
+             f(1)");
+        assert_eq!(
+            results[1..6],
+            [
+                "(2, 1): (Int, Int)",
+                "(2, 1): (Int, Int)",
+                "expected a tuple of 2 components, found `Int`",
+                "expected a tuple of 2 components, found `(Int, Int, Int)`",
+                "expected a tuple of 2 components, found `Int`",
+            ]
+        );
+        assert_eq!(results[8], "2: Int");
+    }
+
+    #[test]
+    fn type_and_reset() {
+        let mut session = Session::new("test", Mode::Repl, &[]);
+        run_in(&mut session, "id = (y) => y");
+        // `:type` neither assigns `z` nor makes `id` take `Bool`.
+        session.print_type(":type z = id(true)", 6);
+        assert_eq!(
+            run_in(&mut session, "id(2)\nz"),
+            ["2: Int", "variable `z` read before it is assigned"]
+        );
+        session.reset();
+        assert_eq!(
+            run_in(&mut session, "id\nid = 3"),
+            ["variable `id` read before it is assigned", "3: Int"]
+        );
+    }
+
+    #[test]
+    fn each_session_has_its_own_types() {
+        let mut first = Session::new("test", Mode::Repl, &[]);
+        assert_eq!(first.tys.num_types(), 0);
+        run_in(&mut first, "x = 1");
+        let num_types = first.tys.num_types();
+        // Neither `:type` nor a rejected statement leaves types behind.
+        first.print_type(":type y = (x, x == 1)", 6);
+        run_in(&mut first, "x = true");
+        assert_eq!(first.tys.num_types(), num_types);
+        let mut second = Session::new("test", Mode::Repl, &[]);
+        assert_eq!(second.tys.num_types(), 0);
+        assert_eq!(run_in(&mut second, "x = true"), ["true: Bool"]);
+        assert_eq!(run_in(&mut first, "x + 1"), ["2: Int"]);
+        drop(first);
+        let mut third = Session::new("test", Mode::Repl, &[]);
+        assert_eq!(third.tys.num_types(), 0);
+        assert_eq!(run_in(&mut third, "x = 2.5"), ["2.5: Float"]);
+        second.reset();
+        assert_eq!(second.tys.num_types(), 0);
+        assert_eq!(run_in(&mut second, "x = 3"), ["3: Int"]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Runs `comp` as a user would, checking its output and exit status.
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Runs `comp` with `args`, giving it `stdin` as standard input.
+fn comp(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_comp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("`comp` must run");
+    child
+        .stdin
+        .take()
+        .expect("standard input must be piped")
+        .write_all(stdin.as_bytes())
+        .expect("standard input must be written");
+    child.wait_with_output().expect("`comp` must finish")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn run_and_check() {
+    let output = comp(&[], "x = 6 * 7\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "Deref[Int](Assign[Int](&v0, Mul(6, 7))): Int\n  -> 42\n"
+    );
+    let output = comp(&["check", "-"], "x = 6 * 7\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn errors_exit_with_1() {
+    let output = comp(&["run", "-"], "x = 1 + true\ny = 2\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).starts_with("error: mismatched types in argument 2\n --> <stdin>:1:9\n")
+    );
+    // The statements after an error still run.
+    assert!(stdout(&output).ends_with("  -> 2\n"));
+    let output = comp(&["check", "no/such/file"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("error: cannot read `no/such/file`"));
+}
+
+#[test]
+fn emit() {
+    let output = comp(&["run", "-", "--emit=ast,values"], "1 + 2\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "(1 + 2)\n3\n");
+    let output = comp(&["check", "-", "--emit=types"], "1 + 2.5\n");
+    assert_eq!(stdout(&output), "Add(ToFloat(1), 2.5): Float\n");
+}
+
+#[test]
+fn usage_errors_exit_with_2() {
+    for args in [
+        &["-"][..],
+        &["--emit=ast"],
+        &["frobnicate"],
+        &["check", "-", "--emit=values"],
+    ] {
+        let output = comp(args, "");
+        assert_eq!(output.status.code(), Some(2), "`comp {}`", args.join(" "));
+        assert!(stderr(&output).contains("\n\nUsage: comp"));
+        assert_eq!(stdout(&output), "");
+    }
+    let output = comp(&["help"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("Usage: comp"));
+}
+
+#[test]
+fn repl_diagnostics_point_into_the_input() {
+    let output = comp(
+        &["repl"],
+        ":env\n:type 1 + true\n:help\nx = 1 + true\n:bogus\ny = 2 +\n  true\n",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    let locations: Vec<_> = stderr.lines().filter(|line| line.contains("-->")).collect();
+    assert_eq!(
+        locations,
+        [" --> <repl>:2:11", " --> <repl>:4:9", " --> <repl>:7:3"]
+    );
+    assert!(
+        stderr.contains("2 | :type 1 + true\n  |           ^^^^ expected `Int`, found `Bool`\n")
+    );
+}